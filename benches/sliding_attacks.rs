@@ -0,0 +1,50 @@
+// Times full attack map rebuilds, and make/unmake pairs which keep the maps up to date.
+// Compare the ray fallback against the PEXT path with
+//   cargo bench --bench sliding_attacks
+//   RUSTFLAGS="-C target-feature=+bmi2" cargo bench --bench sliding_attacks
+use std::hint::black_box;
+use std::time::Instant;
+
+use rust_chess::board::{Board, Color, START_FEN};
+
+const POSITIONS: [(&str, &str); 3] = [
+    ("start", START_FEN),
+    ("kiwipete", "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"),
+    ("sliders", "r1b1kb1r/8/8/3qQ3/8/8/8/R1B1KB1R w - - 0 1"),
+];
+const ITERATIONS: u32 = 500_000;
+const MOVE_ITERATIONS: u32 = 20_000;
+
+fn main() {
+    println!("sliding attacks: {}", if cfg!(all(target_arch = "x86_64", target_feature = "bmi2")) { "pext" } else { "rays" });
+
+    for (name, fen) in POSITIONS {
+        let board = Board::from_fen(fen).unwrap();
+
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            black_box(board.compute_attacks(black_box(Color::White)));
+            black_box(board.compute_attacks(black_box(Color::Black)));
+        }
+        let elapsed = start.elapsed();
+
+        println!("{:>10}: {:.1} ns per attack map", name, elapsed.as_nanos() as f64 / (2 * ITERATIONS) as f64);
+    }
+
+    for (name, fen) in POSITIONS {
+        let mut board = Board::from_fen(fen).unwrap();
+        let moves = board.get_legal_moves();
+
+        let start = Instant::now();
+        for _ in 0..MOVE_ITERATIONS {
+            for &m in moves.iter() {
+                let undo = board.make_move(black_box(m));
+                black_box(&board.attacks);
+                board.unmake_move(undo);
+            }
+        }
+        let elapsed = start.elapsed();
+
+        println!("{:>10}: {:.1} ns per make/unmake", name, elapsed.as_nanos() as f64 / (moves.len() as u32 * MOVE_ITERATIONS) as f64);
+    }
+}
@@ -1,20 +1,62 @@
 use std::fmt;
 //use std::str;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
 use std::collections::HashMap;
+use std::sync::Arc;
 use regex::Regex;
 use lazy_static::lazy_static;
 
+#[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+use crate::pext;
+use crate::fairy::{FairyDef, Registry};
+use crate::zobrist::ZOBRIST;
+
 pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
-pub const PIECE_MAP: [char; 7] = ['.', 'P', 'R', 'N', 'B', 'Q', 'K'];
+pub const CAPABLANCA_FEN: &str = "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR w KQkq - 0 1";
+pub const PIECE_TYPES: usize = 9; // built in ones, including Empty
+pub const PIECE_MAP: [char; PIECE_TYPES] = ['.', 'P', 'R', 'N', 'B', 'Q', 'K', 'A', 'C'];
+pub const MAX_MOVES: usize = 256; // no legal chess position has more than 218 moves
+pub const MAX_FILES: usize = 26; // a-z
+pub const MIN_SIDE: usize = 4; // fewest ranks or files, pawns need a start rank and room to push
+pub type Bitboard = u128; // one bit per square, wide enough for boards up to 128 squares
+
+pub(crate) const ROOK_DIRS: [(i16, i16); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)]; // (rank, file)
+pub(crate) const BISHOP_DIRS: [(i16, i16); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const QUEEN_DIRS: [(i16, i16); 8] = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)];
+const KNIGHT_OFFSETS: [(i16, i16); 8] = [(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)];
+const KING_OFFSETS: [(i16, i16); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+
+// indices of the set bits, lowest first
+pub fn bitboard_squares(mut bb: Bitboard) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || {
+        if bb == 0 {
+            return None;
+        }
+        let index = bb.trailing_zeros() as usize;
+        bb &= bb - 1;
+        Some(index)
+    })
+}
+
 macro_rules! CORRUPT_BOARD_PANIC_MSG{()=>("board hash tables corrupted, bailing...")}
 
-#[derive(Copy,Clone,Eq,PartialEq,Hash,Default)]
+#[derive(Debug,Copy,Clone,Eq,PartialEq,Hash,Default)]
 pub enum Color {
     #[default] White,
                Black,
 }
 
-#[derive(Copy,Clone,Eq,Hash,PartialEq,Default)]
+impl Color {
+    pub fn opposite(&self) -> Color {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+}
+
+#[derive(Debug,Copy,Clone,Eq,Hash,PartialEq,Default)]
 pub enum PieceType {
     #[default] Empty,
                Pawn,
@@ -23,12 +65,91 @@ pub enum PieceType {
                Bishop,
                Queen,
                King,
+               Archbishop, // bishop + knight, Capablanca chess
+               Chancellor, // rook + knight
+               Fairy(u8), // defined at runtime, slot in the board's fairy Registry
+}
+
+// the built in piece types but pawns, which move and capture differently
+const MOVERS: [PieceType; 7] = [PieceType::Rook, PieceType::Knight, PieceType::Bishop, PieceType::Queen,
+                                PieceType::King, PieceType::Archbishop, PieceType::Chancellor];
+
+// what a pawn may become, strongest first so a plain click in the gui makes a queen.
+// capablanca boards (10 files) add the archbishop and chancellor
+const PROMOTIONS: [PieceType; 6] = [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight,
+                                    PieceType::Archbishop, PieceType::Chancellor];
+
+// how a piece moves: single jumps by offset, and slides along directions until blocked.
+// both lists must be symmetric, since attackers_of looks back outwards from the target with them
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+pub struct Movement<'a> {
+    pub leaps: &'a [(i16, i16)], // (rank, file)
+    pub rides: &'a [(i16, i16)],
+}
+
+pub type Offsets = &'static [(i16, i16)]; // (rank, file)
+
+impl PieceType {
+    // empty for pawns, their moves depend on color and what's in front of them. fairies'
+    // movement comes from the board's registry, see Board::movement
+    pub fn movement(&self) -> Movement<'static> {
+        let (leaps, rides): (Offsets, Offsets) = match self {
+            PieceType::Empty | PieceType::Pawn => (&[], &[]),
+            PieceType::Rook       => (&[], &ROOK_DIRS),
+            PieceType::Knight     => (&KNIGHT_OFFSETS, &[]),
+            PieceType::Bishop     => (&[], &BISHOP_DIRS),
+            PieceType::Queen      => (&[], &QUEEN_DIRS),
+            PieceType::King       => (&KING_OFFSETS, &[]),
+            PieceType::Archbishop => (&KNIGHT_OFFSETS, &BISHOP_DIRS),
+            PieceType::Chancellor => (&KNIGHT_OFFSETS, &ROOK_DIRS),
+            PieceType::Fairy(_)   => (&[], &[]),
+        };
+
+        Movement { leaps, rides }
+    }
+
+    // position in per-piece tables: the built in pieces in declaration order, then fairy slots
+    pub fn index(&self) -> usize {
+        match self {
+            PieceType::Empty      => 0,
+            PieceType::Pawn       => 1,
+            PieceType::Rook       => 2,
+            PieceType::Knight     => 3,
+            PieceType::Bishop     => 4,
+            PieceType::Queen      => 5,
+            PieceType::King       => 6,
+            PieceType::Archbishop => 7,
+            PieceType::Chancellor => 8,
+            PieceType::Fairy(slot) => PIECE_TYPES + *slot as usize,
+        }
+    }
+
+    // white's FEN letter, Knight -> 'N'. fairies' letters are in the board's registry, see Board::letter
+    pub fn letter(&self) -> char {
+        PIECE_MAP.get(self.index()).copied().unwrap_or('?')
+    }
+
+    // white's FEN letter, 'N' -> Knight, built in pieces only
+    pub fn from_letter(c: char) -> Option<PieceType> {
+        match c {
+            'P' => Some(PieceType::Pawn),
+            'R' => Some(PieceType::Rook),
+            'N' => Some(PieceType::Knight),
+            'B' => Some(PieceType::Bishop),
+            'Q' => Some(PieceType::Queen),
+            'K' => Some(PieceType::King),
+            'A' => Some(PieceType::Archbishop),
+            'C' => Some(PieceType::Chancellor),
+            _   => None,
+        }
+    }
 }
 
-#[derive(Copy,Clone,Eq,PartialEq,Default)]
+#[derive(Debug,Copy,Clone,Eq,PartialEq,Default)]
 pub enum GameResult {
     #[default] Active,
                DrawAgreement,
+               DrawStalemate,
                DrawThreefold,
                Draw50Moves,
                DrawInsufficientMaterial,
@@ -41,20 +162,187 @@ pub enum GameResult {
                BlackCheckmate,
 }
 
-#[derive(Default,Copy,Clone,Eq,PartialEq)]
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+pub enum Outcome {
+    Winner(Color),
+    Draw,
+}
+
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+pub enum TerminationReason {
+    Checkmate,
+    Stalemate,
+    Resignation,
+    Timeout,
+    Agreement,
+    Threefold,
+    FiftyMoves,
+    InsufficientMaterial,
+    TimeoutInsufficientMaterial,
+}
+
+// who won (or drew) a finished game and why
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+pub struct Termination {
+    pub outcome: Outcome,
+    pub reason: TerminationReason,
+}
+
+impl GameResult {
+    pub fn termination(&self) -> Option<Termination> {
+        let (outcome, reason) = match self {
+            GameResult::Active                          => return None,
+            GameResult::DrawAgreement                   => (Outcome::Draw, TerminationReason::Agreement),
+            GameResult::DrawStalemate                   => (Outcome::Draw, TerminationReason::Stalemate),
+            GameResult::DrawThreefold                   => (Outcome::Draw, TerminationReason::Threefold),
+            GameResult::Draw50Moves                     => (Outcome::Draw, TerminationReason::FiftyMoves),
+            GameResult::DrawInsufficientMaterial        => (Outcome::Draw, TerminationReason::InsufficientMaterial),
+            GameResult::DrawTimeoutInsufficientMaterial => (Outcome::Draw, TerminationReason::TimeoutInsufficientMaterial),
+            GameResult::WhiteTime                       => (Outcome::Winner(Color::White), TerminationReason::Timeout),
+            GameResult::WhiteResign                     => (Outcome::Winner(Color::White), TerminationReason::Resignation),
+            GameResult::WhiteCheckmate                  => (Outcome::Winner(Color::White), TerminationReason::Checkmate),
+            GameResult::BlackTime                       => (Outcome::Winner(Color::Black), TerminationReason::Timeout),
+            GameResult::BlackResign                     => (Outcome::Winner(Color::Black), TerminationReason::Resignation),
+            GameResult::BlackCheckmate                  => (Outcome::Winner(Color::Black), TerminationReason::Checkmate),
+        };
+
+        Some(Termination { outcome, reason })
+    }
+
+    // PGN Result tag / movetext terminator
+    pub fn pgn_result(&self) -> &'static str {
+        match self.termination() {
+            None => "*",
+            Some(Termination { outcome: Outcome::Winner(Color::White), .. }) => "1-0",
+            Some(Termination { outcome: Outcome::Winner(Color::Black), .. }) => "0-1",
+            Some(Termination { outcome: Outcome::Draw, .. }) => "1/2-1/2",
+        }
+    }
+
+    // PGN Termination tag
+    pub fn pgn_termination(&self) -> &'static str {
+        match self.termination() {
+            None => "unterminated",
+            Some(Termination { reason: TerminationReason::Timeout, .. })
+            | Some(Termination { reason: TerminationReason::TimeoutInsufficientMaterial, .. }) => "time forfeit",
+            Some(_) => "normal",
+        }
+    }
+
+    // Inverse of pgn_result/pgn_termination. The tags can't tell a checkmate from a resignation,
+    // so decisive "normal" games come back as resignations and drawn ones as agreements.
+    pub fn from_pgn(result: &str, termination: Option<&str>) -> Option<GameResult> {
+        let time_forfeit = termination.map(|t| t.trim().eq_ignore_ascii_case("time forfeit")).unwrap_or(false);
+
+        match (result.trim(), time_forfeit) {
+            ("*", _)           => Some(GameResult::Active),
+            ("1-0", false)     => Some(GameResult::WhiteResign),
+            ("1-0", true)      => Some(GameResult::WhiteTime),
+            ("0-1", false)     => Some(GameResult::BlackResign),
+            ("0-1", true)      => Some(GameResult::BlackTime),
+            ("1/2-1/2", false) => Some(GameResult::DrawAgreement),
+            ("1/2-1/2", true)  => Some(GameResult::DrawTimeoutInsufficientMaterial),
+            _                  => None,
+        }
+    }
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self.reason {
+            TerminationReason::Checkmate                   => "checkmate",
+            TerminationReason::Stalemate                   => "stalemate",
+            TerminationReason::Resignation                 => "resignation",
+            TerminationReason::Timeout                     => "timeout",
+            TerminationReason::Agreement                   => "agreement",
+            TerminationReason::Threefold                   => "threefold repetition",
+            TerminationReason::FiftyMoves                  => "the 50 move rule",
+            TerminationReason::InsufficientMaterial        => "insufficient material",
+            TerminationReason::TimeoutInsufficientMaterial => "timeout with insufficient material",
+        };
+
+        match self.outcome {
+            Outcome::Winner(Color::White) => write!(f, "White wins by {}", reason),
+            Outcome::Winner(Color::Black) => write!(f, "Black wins by {}", reason),
+            Outcome::Draw                 => write!(f, "Draw by {}", reason),
+        }
+    }
+}
+
+// line a pin runs along, from the king out to the pinning piece
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+pub struct Ray {
+    pub king: usize,
+    pub pinner: usize,
+    pub direction: (i16, i16), // (rank, file) step from the king towards the pinner
+}
+
+// why is_legal rejected a move
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+pub enum MoveError {
+    OffBoard,
+    GameOver,
+    NoPieceOnFrom,
+    WrongColor,
+    IllegalPieceMove, // the piece can't make that move (or the move flags don't match it)
+    LeavesKingInCheck,
+}
+
+// material handicap for odds games, always the queen-side piece
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+pub enum Odds {
+    Knight,
+    Rook,
+    Queen,
+}
+
+// why a hand-edited or imported position was rejected
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+pub enum SetupError {
+    MissingKing(Color),
+    ExtraKing(Color),
+    OpponentInCheck,       // the side not to move could have its king taken
+    PawnOnBackRank(usize),
+    BadEnPassant,          // no pawn could just have double-pushed past the square
+    BadCastling,           // a castling right without the king and rook on their start squares
+}
+
+// what make_move throws away, handed back to unmake_move
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+pub struct Undo {
+    moveop: MoveOp,
+    moved: Square,
+    captured: Square,
+    captured_at: usize, // differs from moveop.to for en passant
+    castling: ((bool, bool), (bool, bool)),
+    en_passant: (bool, usize),
+    halfmove_clock: u16,
+    fullmove_number: u16,
+    result: GameResult,
+}
+
+// what make_null_move throws away, handed back to unmake_null_move
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+pub struct NullMove {
+    en_passant: (bool, usize),
+    halfmove_clock: u16,
+    fullmove_number: u16,
+}
+
+#[derive(Debug,Default,Copy,Clone,Eq,PartialEq)]
 pub struct Square {
     pub color: Color,
     pub piece: PieceType,
 }
 
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct MoveOp {
-    from: usize,
-    to:   usize,
-    is_enpassant: bool,
-    is_castle: bool,
-    set_enpassant: (bool, usize),
-    promote: PieceType,
+    pub from: usize,
+    pub to:   usize,
+    pub is_enpassant: bool,
+    pub is_castle: bool,
+    pub set_enpassant: (bool, usize),
+    pub promote: PieceType,
 }
 
 impl Default for MoveOp {
@@ -70,6 +358,83 @@ impl Default for MoveOp {
     }
 }
 
+// facts about a move in the position it is played from, see Board::classify
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct MoveInfo {
+    pub moveop: MoveOp,
+    pub piece: PieceType,    // what moved
+    pub captured: PieceType, // Empty unless something was taken
+    pub is_capture: bool,
+    pub is_promotion: bool,
+    pub is_double_push: bool,
+    pub is_enpassant: bool,
+    pub is_castle: bool,
+    pub gives_check: bool,
+}
+
+// Move buffer with inline room for MAX_MOVES, lives on the stack so move generation
+// doesn't allocate. Big fairy boards can go past that, then the moves spill to the heap.
+#[derive(Clone)]
+pub struct MoveList {
+    moves: [MoveOp; MAX_MOVES],
+    len: usize,
+    spilled: Vec<MoveOp>, // every move once len passes MAX_MOVES, empty before that
+}
+
+impl MoveList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, m: MoveOp) {
+        if self.len < MAX_MOVES {
+            self.moves[self.len] = m;
+        } else {
+            if self.spilled.is_empty() {
+                self.spilled.extend_from_slice(&self.moves);
+            }
+            self.spilled.push(m);
+        }
+        self.len += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.spilled.clear();
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        Self {
+            moves: [MoveOp::default(); MAX_MOVES],
+            len: 0,
+            spilled: Vec::new(),
+        }
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [MoveOp];
+
+    fn deref(&self) -> &[MoveOp] {
+        if self.len > MAX_MOVES {
+            &self.spilled
+        } else {
+            &self.moves[..self.len]
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a MoveOp;
+    type IntoIter = std::slice::Iter<'a, MoveOp>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
 #[derive(Clone)]
 pub struct Board {
     pub squares: Vec<Square>,
@@ -81,6 +446,10 @@ pub struct Board {
     pub halfmove_clock: u16,
     pub fullmove_number: u16,
     pub result: GameResult,
+    pub attacks: (Bitboard, Bitboard), // squares attacked by (white, black)
+    attacks_from: Vec<Bitboard>, // squares attacked by the piece on each square, kept to update the maps above
+    pub history: Vec<u64>, // keys of the earlier positions in the game, oldest first
+    pub fairies: Arc<Registry>, // the variant's own pieces, shared by the boards it's played on
 }
 
 impl Board {
@@ -97,10 +466,10 @@ impl Board {
                 index = square.piece;
                 color = square.color;
                 if color == Color::White {
-                    board_string.push(PIECE_MAP[index as usize]);
+                    board_string.push(self.letter(index));
                 }
                 else if color == Color::Black {
-                    board_string.push(PIECE_MAP[index as usize].to_lowercase().collect::<Vec<_>>()[0]);
+                    board_string.push(self.letter(index).to_lowercase().collect::<Vec<_>>()[0]);
                 }
                 else {
                     board_string.push(PIECE_MAP[0]);
@@ -118,6 +487,7 @@ impl Board {
                                       match self.result {
                                           GameResult::Active=>"...",
                                           GameResult::DrawAgreement=>"Draw by mutual agreement",
+                                          GameResult::DrawStalemate=>"Stalemate - draw.",
                                           GameResult::DrawThreefold=>"Three-fold repetition - draw.",
                                           GameResult::Draw50Moves=>"50 moves w/o capture or pawn move - draw.",
                                           GameResult::DrawInsufficientMaterial=>"Insufficient material - draw.",
@@ -154,17 +524,35 @@ impl Board {
         board_string
     }
 
-    fn alg_to_index(&self, alg_notation: &str)->usize{
-        let c_str = alg_notation.as_bytes();
-        let file = (c_str[0] - 48) as usize;
-        let rank = (c_str[1] - 48) as usize;
-        
-        rank*self.shape.1 + file
+    // "e4" style square name
+    pub fn index_to_alg(&self, index: usize)->String{
+        let file = (b'a' + (index % self.shape.1) as u8) as char;
+        let rank = self.shape.0 - index / self.shape.1;
+
+        format!("{}{}", file, rank)
+    }
+
+    // inverse of index_to_alg, None for anything that isn't a square on this board
+    pub fn parse_square(&self, name: &str) -> Option<usize> {
+        let mut chars = name.chars();
+        let file = (chars.next()? as usize).checked_sub('a' as usize)?;
+        let rank: usize = chars.as_str().parse().ok()?;
+
+        if file >= self.shape.1 || rank == 0 || rank > self.shape.0 {
+            return None;
+        }
+
+        Some((self.shape.0 - rank) * self.shape.1 + file)
     }
 
     pub fn from_fen(fen_string: &str)->Result<Board, i16> {
+        Board::from_fen_with(fen_string, Arc::default())
+    }
+
+    // from_fen for a variant with fairy pieces, their letters are read through fairies
+    pub fn from_fen_with(fen_string: &str, fairies: Arc<Registry>)->Result<Board, i16> {
         lazy_static!{
-            static ref FEN_EXP: Regex = Regex::new(r"^((?:[rnbqkpRNBQKP1-8]+/?){8})\s+([wb])\s+([KQkq\-]+)\s+([\-a-h1-8]+)\s+(\d)\s+(\d)").unwrap();
+            static ref FEN_EXP: Regex = Regex::new(r"^([a-zA-Z0-9]+(?:/[a-zA-Z0-9]+)*)\s+([wb])\s+([KQkq\-]+)\s+([\-a-z0-9]+)\s+(\d+)\s+(\d+)").unwrap();
         }
 
         let fen_fields = match FEN_EXP.captures_iter(fen_string).next() {
@@ -172,63 +560,57 @@ impl Board {
             None => return Err(1),
         };
 
-        let ranks= fen_fields[1].split('/');
+        let ranks: Vec<&str> = fen_fields[1].split('/').collect();
         let toplay = &fen_fields[2];
         let castling = &fen_fields[3];
         let en_passant = &fen_fields[4];
         let halfmove = &fen_fields[5];
         let fullmove = &fen_fields[6];
 
-        let mut board_index: usize = 0;
-        let mut new_board: Board = Board::default();
-
-        let piececharmap = HashMap::from([
-            ('P', PieceType::Pawn),
-            ('R', PieceType::Rook),
-            ('N', PieceType::Knight),
-            ('B', PieceType::Bishop),
-            ('Q', PieceType::Queen),
-            ('K', PieceType::King),
-        ]);
-
-        // populate board
-        for (_, rank) in ranks.enumerate() {
-            for (_, c) in rank.chars().enumerate() {
-                if c.is_numeric() { // empty squares
-                    for _ in 0..c.to_digit(10).unwrap() {
-                        new_board.squares[board_index] = Square::default();
-                        board_index += 1;
-                    }
-                }
-                else { // piece...
-                    if c.is_uppercase() { // white piece...
-                        new_board.squares[board_index] = Square { 
-                            piece: match piececharmap.get(&c) {
-                                Some(&piece) => piece,
-                                None => PieceType::Empty,
-                            },
-
-                            color: Color::White,
-                        };
-                        //new_board.squares[board_index] = Square { piece: PieceType::Pawn, color: Color::White };
-                        board_index += 1;
-                    }
-                    else { // black piece...
-                        new_board.squares[board_index] = Square { 
-                            piece: match piececharmap.get(&c.to_uppercase().collect::<Vec<_>>()[0]){
-                                Some(&piece) => piece,
-                                None => PieceType::Empty,
-                            }, 
-
-                            color: Color::Black };
-                        board_index += 1;
+        let mut new_board: Board = Board { fairies, ..Board::default() };
+
+        // populate board, one row per rank. runs of empty squares can take more than one digit on wide boards
+        new_board.squares.clear();
+        let mut widths: Vec<usize> = Vec::new();
+        for rank in &ranks {
+            let rank_start = new_board.squares.len();
+            let mut empty: usize = 0;
+            for c in rank.chars() {
+                if let Some(digit) = c.to_digit(10) {
+                    empty = empty * 10 + digit as usize;
+                    if empty > Bitboard::BITS as usize {
+                        return Err(2);
                     }
+                    continue;
                 }
-                
+
+                new_board.squares.extend(std::iter::repeat_n(Square::default(), empty));
+                empty = 0;
+
+                // fairy letters count too, if the registry has them
+                let piece = match new_board.piece_from_letter(c.to_ascii_uppercase()) {
+                    Some(piece) => piece,
+                    None => return Err(1),
+                };
+                new_board.squares.push(Square {
+                    piece,
+                    color: if c.is_uppercase() { Color::White } else { Color::Black },
+                });
             }
+            new_board.squares.extend(std::iter::repeat_n(Square::default(), empty));
+            widths.push(new_board.squares.len() - rank_start);
+        }
+
+        // every rank the same width, files a-z, and few enough squares for a Bitboard
+        let width = widths[0];
+        if widths.iter().any(|&w| w != width) || !(MIN_SIDE..=MAX_FILES).contains(&width)
+            || ranks.len() < MIN_SIDE || new_board.squares.len() > Bitboard::BITS as usize {
+            return Err(2);
         }
+        new_board.shape = (ranks.len(), width);
 
         new_board.populate_map();
+        new_board.update_attacks();
 
         // set board state
         if toplay == "w" {
@@ -254,38 +636,184 @@ impl Board {
             new_board.castling.1.1 = true;
         }
 
-        new_board.halfmove_clock = halfmove.parse::<u16>().unwrap();
-        new_board.fullmove_number = fullmove.parse::<u16>().unwrap();
+        new_board.halfmove_clock = match halfmove.parse::<u16>() {
+            Ok(n) => n,
+            Err(_) => return Err(1),
+        };
+        new_board.fullmove_number = match fullmove.parse::<u16>() {
+            Ok(n) => n,
+            Err(_) => return Err(1),
+        };
 
         if en_passant != "-" {
-            new_board.en_passant = (true, new_board.alg_to_index(en_passant));
+            let index = match new_board.parse_square(en_passant) {
+                Some(index) => index,
+                None => return Err(3),
+            };
+
+            // the square the last double step skipped: on the mover's third rank, empty, and with
+            // the pawn that made it right in front. capturing en passant takes that pawn off
+            let mover = new_board.to_play.opposite();
+            let (third_rank, ahead) = match mover {
+                Color::White => (new_board.shape.0 - 3, -1),
+                Color::Black => (2, 1),
+            };
+            let pawn = new_board.offset_index(index, ahead, 0).map(|i| new_board.squares[i]);
+            if index / new_board.shape.1 != third_rank || new_board.squares[index].piece != PieceType::Empty
+                || pawn != Some(Square{piece: PieceType::Pawn, color: mover}) {
+                return Err(3);
+            }
+            new_board.en_passant = (true, index);
         }
 
         new_board.result = GameResult::Active;
+        new_board.update_result();
 
 
         Ok(new_board)
     }
 
+    // inverse of from_fen, runs of more than nine empty squares (wide boards) are written as one number
+    pub fn to_fen(&self) -> String {
+        let (height, width) = self.shape;
+        let mut ranks = Vec::with_capacity(height);
+
+        for row in 0..height {
+            let mut rank = String::new();
+            let mut empty = 0;
+            for square in &self.squares[row * width..(row + 1) * width] {
+                if square.piece == PieceType::Empty {
+                    empty += 1;
+                    continue;
+                }
+                if empty > 0 {
+                    rank.push_str(&empty.to_string());
+                    empty = 0;
+                }
+                rank.push(match square.color {
+                    Color::White => self.letter(square.piece),
+                    Color::Black => self.letter(square.piece).to_ascii_lowercase(),
+                });
+            }
+            if empty > 0 {
+                rank.push_str(&empty.to_string());
+            }
+            ranks.push(rank);
+        }
+
+        let ((white_k, white_q), (black_k, black_q)) = self.castling;
+        let castling: String = [(white_k, 'K'), (white_q, 'Q'), (black_k, 'k'), (black_q, 'q')].iter()
+            .filter(|(right, _)| *right)
+            .map(|&(_, c)| c)
+            .collect();
+
+        format!("{} {} {} {} {} {}",
+            ranks.join("/"),
+            match self.to_play {
+                Color::White => "w",
+                Color::Black => "b",
+            },
+            if castling.is_empty() { "-".to_string() } else { castling },
+            if self.en_passant.0 { self.index_to_alg(self.en_passant.1) } else { "-".to_string() },
+            self.halfmove_clock,
+            self.fullmove_number,
+        )
+    }
+
+    // like from_fen, but tolerates stray whitespace, missing trailing fields (EPD style, or
+    // clocks only) and trailing EPD operations, filling in "w - - 0 1" defaults
+    pub fn from_fen_lenient(fen_string: &str)->Result<Board, i16> {
+        const DEFAULTS: [&str; 6] = ["", "w", "-", "-", "0", "1"];
+
+        let mut fields: Vec<&str> = fen_string.split_whitespace().take(4).collect();
+        if fields.is_empty() {
+            return Err(1);
+        }
+
+        if fields.len() == 4 {
+            fields.extend(fen_string.split_whitespace().skip(4).take(2).take_while(|f| f.parse::<u16>().is_ok()));
+        }
+
+        fields.extend_from_slice(&DEFAULTS[fields.len()..]);
+
+        Board::from_fen(&fields.join(" "))
+    }
+
+    // standard start with giver's b-knight, a-rook or queen taken off
+    pub fn odds_start(giver: Color, odds: Odds) -> Board {
+        let mut board = Board::from_fen(START_FEN).unwrap();
+        let back_rank = match giver {
+            Color::White => (board.shape.0 - 1) * board.shape.1,
+            Color::Black => 0,
+        };
+
+        let file = match odds {
+            Odds::Knight => 1,
+            Odds::Rook   => 0,
+            Odds::Queen  => 3,
+        };
+        board.squares[back_rank + file] = Square::default();
+
+        if odds == Odds::Rook {
+            match giver {
+                Color::White => board.castling.0.1 = false,
+                Color::Black => board.castling.1.1 = false,
+            }
+        }
+
+        board.populate_map();
+        board.update_attacks();
+
+        board
+    }
+
+    // the definition of a fairy piece on this board, None for built in pieces
+    pub fn fairy(&self, piece: PieceType) -> Option<&FairyDef> {
+        self.fairies.get(piece)
+    }
+
+    pub fn movement(&self, piece: PieceType) -> Movement<'_> {
+        match self.fairy(piece) {
+            Some(def) => def.movement(),
+            None => piece.movement(),
+        }
+    }
+
+    // white's FEN letter, fairies' included
+    pub fn letter(&self, piece: PieceType) -> char {
+        self.fairy(piece).map_or(piece.letter(), |def| def.letter)
+    }
+
+    // white's FEN letter back to the piece, fairies' included
+    pub fn piece_from_letter(&self, c: char) -> Option<PieceType> {
+        PieceType::from_letter(c).or_else(|| self.fairies.from_letter(c))
+    }
+
+    // every piece type that moves by its Movement, this board's fairies included
+    fn movers(&self) -> impl Iterator<Item = PieceType> + '_ {
+        MOVERS.into_iter().chain(self.fairies.pieces())
+    }
+
     fn search_piece(&self, p: PieceType) -> Vec<usize>{
         self.squares.iter().enumerate().filter_map(|s| {
             if p == s.1.piece {
-                Some(s.0 as usize)
+                Some(s.0)
             } else {
                 None
             }
         }).collect::<Vec<_>>()
     }
 
-    fn get_table(&self, p: PieceType) -> Vec<usize>{
-        match self.piece_map.get(&p){
-            Some(l) => l.clone(),
-            None => panic!(CORRUPT_BOARD_PANIC_MSG!()),
+    fn get_table(&self, p: PieceType) -> &[usize]{
+        match (self.piece_map.get(&p), p) {
+            (Some(l), _) => l,
+            (None, PieceType::Fairy(_)) => &[], // not in this board's registry, so none on it
+            (None, _) => panic!(CORRUPT_BOARD_PANIC_MSG!()),
         }
     }
 
-    fn get_table_colored(&self, p: PieceType, c: Color) -> Vec<usize> {
-        self.get_table(p).into_iter().filter(|&m| self.squares[m].color == c).collect()
+    fn get_table_colored(&self, p: PieceType, c: Color) -> impl Iterator<Item = usize> + '_ {
+        self.get_table(p).iter().copied().filter(move |&m| self.squares[m].color == c)
     }
     
     fn get_mut_table(&mut self, p: PieceType) -> &mut Vec<usize>{
@@ -300,7 +828,7 @@ impl Board {
         self.get_mut_table(p).into_iter().filter(|&m| self.squares[m].color == self.to_play).collect()
     }  */
 
-    fn get_table_index(table: &Vec<usize>, val: usize) -> usize {
+    fn get_table_index(table: &[usize], val: usize) -> usize {
         match table.iter().position(|&r| r == val){
             Some(x) => x,
             None => panic!(CORRUPT_BOARD_PANIC_MSG!()),
@@ -315,10 +843,17 @@ impl Board {
             (PieceType::Knight, self.search_piece(PieceType::Knight)),
             (PieceType::Rook, self.search_piece(PieceType::Rook)),
             (PieceType::Pawn, self.search_piece(PieceType::Pawn)),
+            (PieceType::Archbishop, self.search_piece(PieceType::Archbishop)),
+            (PieceType::Chancellor, self.search_piece(PieceType::Chancellor)),
         ]);
+        for p in self.fairies.pieces().collect::<Vec<_>>() {
+            self.piece_map.insert(p, self.search_piece(p));
+        }
     }
 
     fn apply_move(&mut self, moveop: MoveOp){
+        let previous_key = self.key();
+        let touched = self.touched_squares(moveop, self.squares[moveop.from].color);
         let from_table = self.get_mut_table(self.squares[moveop.from].piece);
 
         let from_index = Self::get_table_index(from_table, moveop.from);
@@ -339,7 +874,7 @@ impl Board {
         // deal with en passant...
         if moveop.is_enpassant {
             capture = true;
-            let backwards_dir: i16 = match self.squares[from_index].color {
+            let backwards_dir: i16 = match self.squares[moveop.from].color {
                 Color::White =>  1,
                 Color::Black => -1,
             };
@@ -350,6 +885,7 @@ impl Board {
             let to_index = Self::get_table_index(to_table, target_pawn_index);
 
             to_table.remove(to_index);
+            self.squares[target_pawn_index] = Square::default();
         }
 
         if moveop.set_enpassant.0 {
@@ -359,40 +895,46 @@ impl Board {
         }
 
         // deal with castling...
-        if self.squares[from_index].piece == PieceType::Rook {
-            let castle: &mut (bool, bool) = match self.squares[from_index].color {
+        let captured = self.squares[moveop.to];
+        if captured.piece == PieceType::Rook && moveop.to / self.shape.1 == self.back_rank(captured.color) {
+            let castle: &mut (bool, bool) = match captured.color {
+                Color::White => &mut self.castling.0,
+                Color::Black => &mut self.castling.1,
+            };
+
+            match moveop.to % self.shape.1 {
+                0 => castle.1 = false,
+                file if file == self.shape.1 - 1 => castle.0 = false,
+                _ => (),
+            }
+        }
+
+        if self.squares[moveop.from].piece == PieceType::Rook {
+            let castle: &mut (bool, bool) = match self.squares[moveop.from].color {
                 Color::White => &mut self.castling.0,
                 Color::Black => &mut self.castling.1,
             };
 
-            if castle.0 && (from_index % self.shape.1 == self.shape.1 - 1){ // king side
+            if castle.0 && (moveop.from % self.shape.1 == self.shape.1 - 1){ // king side
                 castle.0 = false;
-            } else if castle.1 && (from_index % self.shape.1 == 0) { // queen side
+            } else if castle.1 && moveop.from.is_multiple_of(self.shape.1) { // queen side
                 castle.1 = false;
             }
 
-        } else if self.squares[from_index].piece == PieceType::King {
+        } else if self.squares[moveop.from].piece == PieceType::King {
             if moveop.is_castle {
-                // Create a secondary move that isn't a castle, but moves the rook to where it needs to go
-                let castle_from_index: usize;
-                let castle_to_index: usize;
-
-                if (moveop.from as i16) - (moveop.to as i16) > 0 { // queen side
-                    castle_from_index = moveop.from - 4;
-                    castle_to_index = moveop.to + 1;
-                } else { // king side
-                    castle_from_index = moveop.from + 3;
-                    castle_to_index = moveop.to - 1;
-                }
+                // the rook jumps to the king's other side, the king itself is moved below
+                let (_, rook_from, rook_to) = self.castle_squares(moveop.from, moveop.to > moveop.from);
 
-                self.apply_move(MoveOp {
-                    from: castle_from_index,
-                    to: castle_to_index,
-                    ..Default::default()
-                })
+                let rook_table = self.get_mut_table(PieceType::Rook);
+                let rook_index = Self::get_table_index(rook_table, rook_from);
+                rook_table[rook_index] = rook_to;
+
+                self.squares[rook_to] = self.squares[rook_from];
+                self.squares[rook_from] = Square::default();
             }
             
-            if self.squares[from_index].color == Color::White {
+            if self.squares[moveop.from].color == Color::White {
                 self.castling.0 = (false, false);
             } else {
                 self.castling.1 = (false, false);
@@ -400,18 +942,24 @@ impl Board {
         }
 
         // deal with 50 move rule...
-        if capture || self.squares[from_index].piece == PieceType::Pawn {
-            self.halfmove_clock = 50;
+        if capture || self.squares[moveop.from].piece == PieceType::Pawn {
+            self.halfmove_clock = 0;
         } else {
-            self.halfmove_clock -= 1;
-        }
-
-        if self.halfmove_clock == 0 {
-            self.result = GameResult::Draw50Moves;
+            self.halfmove_clock = self.halfmove_clock.saturating_add(1); // from_fen takes any u16
         }
 
         self.squares[moveop.to] = self.squares[moveop.from];
-        self.squares[moveop.from].piece = PieceType::Empty;
+        self.squares[moveop.from] = Square::default(); // same as what from_fen puts on empty squares
+
+        // the pawn was filed under its new square above, move it over to the new piece's table
+        if moveop.promote != PieceType::Empty {
+            let pawn_table = self.get_mut_table(PieceType::Pawn);
+            let pawn_index = Self::get_table_index(pawn_table, moveop.to);
+            pawn_table.remove(pawn_index);
+
+            self.get_mut_table(moveop.promote).push(moveop.to);
+            self.squares[moveop.to].piece = moveop.promote;
+        }
 
         self.to_play = match self.to_play {
             Color::Black => Color::White,
@@ -419,226 +967,715 @@ impl Board {
         };
 
         if self.to_play == Color::White {
-            self.fullmove_number += 1;
+            self.fullmove_number = self.fullmove_number.saturating_add(1);
         }
-    }
 
-    pub fn apply_move_nomut(&self, moveop: MoveOp) -> Self {
-        let mut child: Self = self.clone();
-        child.apply_move(moveop);
+        self.update_attacks_around(touched);
 
-        child
+        self.history.push(previous_key);
     }
 
-    fn get_sliding_moves_single(&self, piece: PieceType, start_index: usize)->Vec<MoveOp> {
-        let start_sq = self.squares[start_index];
-        let mut moves: Vec<MoveOp> = Vec::new();
+    // how many times the current position has come up, this time included. nothing from
+    // before the last capture or pawn move can come back, so only that far is searched
+    pub fn repetitions(&self) -> usize {
+        let key = self.key();
+        1 + self.history.iter().rev().take(self.halfmove_clock as usize).filter(|&&k| k == key).count()
+    }
 
-        let mut index: i16 = 0;
-        
-        let mut target: Square;
+    // apply_move, handing back what it takes to undo it again with unmake_move
+    pub fn make_move(&mut self, moveop: MoveOp) -> Undo {
+        let captured_at = if moveop.is_enpassant {
+            match self.squares[moveop.from].color {
+                Color::White => moveop.to + self.shape.1,
+                Color::Black => moveop.to - self.shape.1,
+            }
+        } else {
+            moveop.to
+        };
+
+        let undo = Undo {
+            moveop,
+            moved: self.squares[moveop.from],
+            captured: self.squares[captured_at],
+            captured_at,
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            result: self.result,
+        };
+        self.apply_move(moveop);
 
-        let mut incs: Vec<i16> = Vec::new();
-        let mut newmove: MoveOp;
-        let rook_incs: Vec<i16> = vec![8, -8, 1, -1];
-        let bishop_incs: Vec<i16> = vec![9, 7, -7, -9];
+        undo
+    }
 
-        if piece == PieceType::Rook{
-            incs.extend(&rook_incs);
-        }
-        else if piece == PieceType::Bishop {
-            incs.extend(&bishop_incs);
-        }
-        else if piece == PieceType::Queen {
-            incs.extend(&rook_incs);
-            incs.extend(&bishop_incs);
+    // take back the move make_move returned undo for, which must be the last one made
+    pub fn unmake_move(&mut self, undo: Undo) {
+        let m = undo.moveop;
+
+        self.to_play = self.to_play.opposite();
+        self.castling = undo.castling;
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.fullmove_number = undo.fullmove_number;
+        self.result = undo.result;
+        self.history.pop();
+
+        // the mover goes home, as a pawn again if it promoted
+        let landed = self.squares[m.to].piece;
+        let table = self.get_mut_table(landed);
+        let index = Self::get_table_index(table, m.to);
+        if landed == undo.moved.piece {
+            table[index] = m.from;
+        } else {
+            table.remove(index);
+            self.get_mut_table(undo.moved.piece).push(m.from);
         }
+        self.squares[m.to] = Square::default();
+        self.squares[m.from] = undo.moved;
 
-        for inc in incs{ // down, up, left, right
-            let mut eob_flag: bool = false;
-            loop {
-                index += inc;
-                let target_index = ((start_index as i16) + index) as usize;
-
-                if target_index >= self.shape.0 * self.shape.1 || eob_flag {
-                    break;
-                }
+        if undo.captured.piece != PieceType::Empty {
+            self.get_mut_table(undo.captured.piece).push(undo.captured_at);
+            self.squares[undo.captured_at] = undo.captured;
+        }
 
-                if target_index % self.shape.1 == 0|| target_index % self.shape.1 == self.shape.1 - 1 {
-                    eob_flag = true;
-                }
-                
-                target = self.squares[target_index];
-                
-                if target.color == start_sq.color {
-                    break;
-                }
+        if m.is_castle {
+            let (_, rook_from, rook_to) = self.castle_squares(m.from, m.to > m.from);
 
-                newmove = MoveOp {
-                    from: start_index,
-                    to: target_index,
-                    ..Default::default()
-                };
+            let rook_table = self.get_mut_table(PieceType::Rook);
+            let rook_index = Self::get_table_index(rook_table, rook_to);
+            rook_table[rook_index] = rook_from;
 
-                if (target.color != start_sq.color) && (target.piece != PieceType::Empty) {
-                    moves.push(newmove);
-                    break;
-                }
-                index = 0;
-            }
-            index = 0;
+            self.squares[rook_from] = self.squares[rook_to];
+            self.squares[rook_to] = Square::default();
         }
 
-        moves
+        self.update_attacks_around(self.touched_squares(m, undo.moved.color));
     }
-    
-    fn get_sliding_moves(&self, piece: PieceType)->Vec<MoveOp> {
-        let indices: Vec<usize> = self.get_table_colored(piece, self.to_play);
-        let mut moves: Vec<MoveOp> = Vec::new();
 
+    // stricter than from_fen: could this position come up in a real game?
+    pub fn validate(&self) -> Result<(), SetupError> {
+        let (height, width) = self.shape;
 
-        for start_index in indices {
-            moves.append(&mut self.get_sliding_moves_single(piece, start_index));
+        for c in [Color::White, Color::Black] {
+            match self.get_table_colored(PieceType::King, c).count() {
+                0 => return Err(SetupError::MissingKing(c)),
+                1 => (),
+                _ => return Err(SetupError::ExtraKing(c)),
+            }
         }
 
-        moves
-    }
+        if self.is_in_check(self.to_play.opposite()) {
+            return Err(SetupError::OpponentInCheck);
+        }
 
-    fn get_knight_moves_single(&self, start_index: usize)->Vec<MoveOp> {
-        let mut moves: Vec<MoveOp> = Vec::new();
-        let start_sq = self.squares[start_index as usize];
-        let mut target_sq: Square;
-        let mut index_horiz_shift: i16;
-        let mut dist_closest_edge: i16;
-        let incs: Vec<i16> = vec![-10, -6, -17, -15, 6, 10, 16, 17];
-        let loc = ((start_index as i16) >> 3, (start_index as i16) - ((start_index as i16) & 0x7ff8));
-    
-        for inc in incs { // all knight moves
-            let target_index = ((start_index as i16) + inc) as usize;
-            let target_loc = ((target_index as i16) >> 3, (target_index as i16) - ((target_index as i16) & 0x7ff8));
-            index_horiz_shift = target_loc.1 - loc.1;
+        if let Some(index) = self.get_table(PieceType::Pawn).iter().find(|&&i| i / width == 0 || i / width == height - 1) {
+            return Err(SetupError::PawnOnBackRank(*index));
+        }
 
-            if loc.1 < 4 {
-                dist_closest_edge = loc.1;
-            } else {
-                dist_closest_edge = 8 - loc.1;
-            }
-            
-            if target_index >= self.shape.0 * self.shape.1
-            || index_horiz_shift.abs() > dist_closest_edge {
-                continue;
+        if self.en_passant.0 {
+            // the square the pawn skipped, with the pawn just past it and its origin now empty
+            let (ep, mover) = (self.en_passant.1, self.to_play.opposite());
+            let (pawn_rank, dr) = match mover {
+                Color::White => (height - 4, 1),
+                Color::Black => (3, -1),
+            };
+            let pawn = self.offset_index(ep, -dr, 0);
+            let origin = self.offset_index(ep, dr, 0);
+
+            let consistent = match (pawn, origin) {
+                (Some(pawn), Some(origin)) => pawn / width == pawn_rank
+                    && self.squares[pawn] == Square{piece: PieceType::Pawn, color: mover}
+                    && self.squares[ep].piece == PieceType::Empty
+                    && self.squares[origin].piece == PieceType::Empty,
+                _ => false,
+            };
+            if !consistent {
+                return Err(SetupError::BadEnPassant);
             }
+        }
 
-            target_sq = self.squares[target_index as usize];
+        for (c, (kingside, queenside)) in [(Color::White, self.castling.0), (Color::Black, self.castling.1)] {
+            let back_rank = match c {
+                Color::White => (height - 1) * width,
+                Color::Black => 0,
+            };
+            let king = Square{piece: PieceType::King, color: c};
+            let rook = Square{piece: PieceType::Rook, color: c};
 
-            if target_sq.color == start_sq.color {
-                continue;
+            if (kingside || queenside) && self.squares[back_rank + width / 2] != king
+                || kingside && self.squares[back_rank + width - 1] != rook
+                || queenside && self.squares[back_rank] != rook {
+                return Err(SetupError::BadCastling);
             }
-
-            moves.push(MoveOp {
-                from: start_index,
-                to: target_index,
-                ..Default::default()
-            });
         }
 
-        moves
+        Ok(())
     }
 
-    fn get_knight_moves(&self)->Vec<MoveOp> {
-        let indices = self.get_table_colored(PieceType::Knight, self.to_play);
-        let mut moves: Vec<MoveOp> = Vec::new();
+    // for the board editor: hand the move to c, refusing positions that fail validate.
+    // squares may have been edited directly, so the piece tables and attack maps are rebuilt
+    pub fn set_to_play(&mut self, c: Color) -> Result<(), SetupError> {
+        self.history.clear(); // edited, no longer the position the game reached
+        self.populate_map();
+        self.update_attacks();
+
+        let previous = (self.to_play, self.en_passant);
+        if c != self.to_play {
+            self.to_play = c;
+            self.en_passant = (false, 0); // only ever capturable by the side that was to move
+        }
 
-        for start_index in indices {
-            moves.append(&mut self.get_knight_moves_single(start_index));
+        if let Err(e) = self.validate() {
+            (self.to_play, self.en_passant) = previous;
+            return Err(e);
         }
-        
-        moves
-    }
-    
-    fn get_king_moves(&self)->Vec<MoveOp> {
-        let indices = self.get_table_colored(PieceType::King, self.to_play);
-        let mut moves: Vec<MoveOp> = Vec::new();
-        for start_index in indices {
-            let start_sq = self.squares[start_index];
-            let incs: Vec<i16> = vec![-9, -8, -7, -1, 1, 7, 8, 9];
-        
-            for inc in incs { // all king moves
-                let target_index = ((start_index as i16) + inc) as usize;
-                let target_loc: (i16, i16) = ((target_index as i16) >> 3, (target_index as i16) - ((target_index as i16) & 0x7ff8));
-                let loc: (i16, i16) = ((start_index as i16) >> 3, (target_index as i16) - ((target_index as i16) & 0x7ff8));
 
-                if ((target_loc.1 - loc.1).abs() > 1)
-                || (target_index >= self.shape.0 * self.shape.1) {
-                    continue;
-                }
+        if matches!(self.result, GameResult::WhiteCheckmate | GameResult::BlackCheckmate | GameResult::DrawStalemate) {
+            self.result = GameResult::Active;
+        }
+        self.update_result();
 
-                let target_sq = self.squares[target_index];
+        Ok(())
+    }
 
-                if target_sq.color == start_sq.color {
-                    continue;
-                }
+    // pass the turn without moving: side to move flips, en passant lapses, clocks tick
+    pub fn make_null_move(&mut self) -> NullMove {
+        let undo = NullMove {
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+        };
 
-                moves.push(MoveOp{
-                    from: start_index,
-                    to: target_index,
-                    ..Default::default()
-                });
-            }
+        self.history.push(self.key());
+        self.en_passant = (false, 0);
+        self.halfmove_clock = self.halfmove_clock.saturating_add(1);
+        if self.to_play == Color::Black {
+            self.fullmove_number = self.fullmove_number.saturating_add(1);
         }
-        
-        moves
+        self.to_play = self.to_play.opposite();
+
+        undo
     }
 
-    fn get_pawn_moves_single(&self, start_index: usize, c: Color)->Vec<MoveOp> {
-        let mut moves: Vec<MoveOp> = Vec::new();
+    pub fn unmake_null_move(&mut self, undo: NullMove) {
+        self.to_play = self.to_play.opposite();
+        self.fullmove_number = undo.fullmove_number;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.en_passant = undo.en_passant;
+        self.history.pop();
+    }
 
-        let direction: i16 = match c {
-            Color::White => -1,
-            Color::Black =>  1,
-        };
+    // step (rank, file) away from index, None if that falls off the board
+    fn offset_index(&self, index: usize, dr: i16, df: i16) -> Option<usize> {
+        let rank = (index / self.shape.1) as i16 + dr;
+        let file = (index % self.shape.1) as i16 + df;
 
-        let advance1: usize = start_index + (direction * self.shape.1 as i16) as usize;
-        
-        if self.squares[advance1].piece == PieceType::Empty {
-            moves.push(MoveOp {
-                from: start_index,
-                to: advance1,
-                ..Default::default()
-            });
+        if rank < 0 || rank >= self.shape.0 as i16 || file < 0 || file >= self.shape.1 as i16 {
+            return None;
+        }
 
-            let advance2: usize = start_index + (2 * direction * self.shape.1 as i16) as usize;
+        Some(rank as usize * self.shape.1 + file as usize)
+    }
+
+    fn occupancy(&self) -> Bitboard {
+        self.piece_map.values().flatten().fold(0, |occupied, &index| occupied | 1 << index)
+    }
+
+    // classic ray walk, works for any board shape
+    fn ray_attacks(&self, start_index: usize, dirs: &[(i16, i16)], occupied: Bitboard) -> Bitboard {
+        let mut attacks: Bitboard = 0;
+
+        for &(dr, df) in dirs {
+            let mut index = start_index;
+            while let Some(target_index) = self.offset_index(index, dr, df) {
+                attacks |= 1 << target_index;
+                if occupied & (1 << target_index) != 0 {
+                    break;
+                }
+                index = target_index;
+            }
+        }
+
+        attacks
+    }
+
+    fn rook_attacks(&self, start_index: usize, occupied: Bitboard) -> Bitboard {
+        #[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+        if self.shape == (8, 8) {
+            return pext::rook_attacks(start_index, occupied as u64) as Bitboard;
+        }
+
+        self.ray_attacks(start_index, &ROOK_DIRS, occupied)
+    }
+
+    fn bishop_attacks(&self, start_index: usize, occupied: Bitboard) -> Bitboard {
+        #[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+        if self.shape == (8, 8) {
+            return pext::bishop_attacks(start_index, occupied as u64) as Bitboard;
+        }
+
+        self.ray_attacks(start_index, &BISHOP_DIRS, occupied)
+    }
+
+    fn leaper_attacks(&self, start_index: usize, offsets: &[(i16, i16)]) -> Bitboard {
+        offsets.iter()
+            .filter_map(|&(dr, df)| self.offset_index(start_index, dr, df))
+            .fold(0, |attacks, target_index| attacks | 1 << target_index)
+    }
+
+    // sliding attacks along rides, rook and bishop lines go through the (possibly PEXT) fast paths
+    fn ride_attacks(&self, start_index: usize, rides: &[(i16, i16)], occupied: Bitboard) -> Bitboard {
+        let orthogonal = ROOK_DIRS.iter().all(|d| rides.contains(d));
+        let diagonal = BISHOP_DIRS.iter().all(|d| rides.contains(d));
+        let mut attacks: Bitboard = 0;
+
+        if orthogonal {
+            attacks |= self.rook_attacks(start_index, occupied);
+        }
+        if diagonal {
+            attacks |= self.bishop_attacks(start_index, occupied);
+        }
+
+        for direction in rides {
+            let covered = (orthogonal && ROOK_DIRS.contains(direction)) || (diagonal && BISHOP_DIRS.contains(direction));
+            if !covered {
+                attacks |= self.ray_attacks(start_index, std::slice::from_ref(direction), occupied);
+            }
+        }
+
+        attacks
+    }
+
+    fn movement_attacks(&self, start_index: usize, movement: Movement<'_>, occupied: Bitboard) -> Bitboard {
+        self.leaper_attacks(start_index, movement.leaps) | self.ride_attacks(start_index, movement.rides, occupied)
+    }
+
+    // squares attacked by the piece on start_index (pawns attack diagonally forward only)
+    fn piece_attacks(&self, start_index: usize, occupied: Bitboard) -> Bitboard {
+        let square = self.squares[start_index];
+        self.attacks_as(square, start_index, occupied)
+    }
+
+    // squares a given piece would attack from start_index, whatever is there now
+    fn attacks_as(&self, square: Square, start_index: usize, occupied: Bitboard) -> Bitboard {
+        match square.piece {
+            PieceType::Empty  => 0,
+            PieceType::Pawn   => {
+                let direction: i16 = match square.color {
+                    Color::White => -1,
+                    Color::Black =>  1,
+                };
+                self.leaper_attacks(start_index, &[(direction, -1), (direction, 1)])
+            },
+            piece => self.movement_attacks(start_index, self.movement(piece), occupied),
+        }
+    }
+
+    // recompute from scratch every square attacked by color c
+    pub fn compute_attacks(&self, c: Color) -> Bitboard {
+        let occupied = self.occupancy();
+        let mut attacks: Bitboard = 0;
+        for table in self.piece_map.values() {
+            for &index in table {
+                if self.squares[index].color == c {
+                    attacks |= self.piece_attacks(index, occupied);
+                }
+            }
+        }
+
+        attacks
+    }
+
+    // rebuild the cached attack maps from scratch, for positions set up or edited wholesale
+    fn update_attacks(&mut self) {
+        self.attacks_from = vec![0; self.squares.len()];
+        self.update_attacks_around(Bitboard::MAX >> (Bitboard::BITS as usize - self.squares.len()));
+    }
+
+    // refresh the attack maps after the squares in touched changed. only the pieces on them and
+    // the riders whose lines run into them can attack differently, the rest keep what they had
+    fn update_attacks_around(&mut self, touched: Bitboard) {
+        let occupied = self.occupancy();
+        for index in bitboard_squares(touched & !occupied) {
+            self.attacks_from[index] = 0;
+        }
+
+        self.attacks = (0, 0);
+        for (&piece, table) in &self.piece_map {
+            let rides = !self.movement(piece).rides.is_empty();
+            for &index in table {
+                if touched & 1 << index != 0 || rides && self.attacks_from[index] & touched != 0 {
+                    self.attacks_from[index] = self.piece_attacks(index, occupied);
+                }
+                match self.squares[index].color {
+                    Color::White => self.attacks.0 |= self.attacks_from[index],
+                    Color::Black => self.attacks.1 |= self.attacks_from[index],
+                }
+            }
+        }
+    }
+
+    // the squares a move changes: where the mover leaves and lands, the pawn taken en passant
+    // and the castling rook's two squares
+    fn touched_squares(&self, moveop: MoveOp, mover: Color) -> Bitboard {
+        let mut touched: Bitboard = 1 << moveop.from | 1 << moveop.to;
+        if moveop.is_enpassant {
+            touched |= 1 << match mover {
+                Color::White => moveop.to + self.shape.1,
+                Color::Black => moveop.to - self.shape.1,
+            };
+        }
+        if moveop.is_castle {
+            let (_, rook_from, rook_to) = self.castle_squares(moveop.from, moveop.to > moveop.from);
+            touched |= 1 << rook_from | 1 << rook_to;
+        }
+
+        touched
+    }
+
+    pub fn get_attack_map(&self, c: Color) -> Bitboard {
+        match c {
+            Color::White => self.attacks.0,
+            Color::Black => self.attacks.1,
+        }
+    }
+
+    // straight from the cached map, pawns only count where they capture
+    pub fn is_square_attacked(&self, index: usize, by: Color) -> bool {
+        self.get_attack_map(by) & (1 << index) != 0
+    }
+
+    // the attack map as square indices, in board order
+    pub fn attack_set(&self, by: Color) -> impl Iterator<Item = usize> + '_ {
+        let map = self.get_attack_map(by);
+        (0..self.squares.len()).filter(move |&index| map & (1 << index) != 0)
+    }
+
+    // squares of color c's pieces attacking (or defending) target
+    pub fn attackers_of(&self, target: usize, c: Color) -> Bitboard {
+        let occupied = self.occupancy();
+        let pieces = |p: PieceType| self.get_table_colored(p, c).fold(0, |bb: Bitboard, index| bb | 1 << index);
+
+        // look outwards from the target with each piece's own pattern, pawns reversed
+        let pawn_dir: i16 = match c {
+            Color::White =>  1,
+            Color::Black => -1,
+        };
+
+        self.movers().fold(self.leaper_attacks(target, &[(pawn_dir, -1), (pawn_dir, 1)]) & pieces(PieceType::Pawn), |attackers, p| {
+            attackers | (self.movement_attacks(target, self.movement(p), occupied) & pieces(p))
+        })
+    }
+
+    // pieces of color c that can't leave the line between their king and an enemy slider
+    pub fn pinned(&self, c: Color) -> Vec<(usize, Ray)> {
+        let mut pins: Vec<(usize, Ray)> = Vec::new();
+        let king = match self.get_table_colored(PieceType::King, c).next() {
+            Some(king) => king,
+            None => return pins,
+        };
+
+        for &direction in QUEEN_DIRS.iter() {
+            let mut candidate: Option<usize> = None;
+            let mut index = king;
+
+            while let Some(target_index) = self.offset_index(index, direction.0, direction.1) {
+                index = target_index;
+                let target = self.squares[index];
+
+                if target.piece == PieceType::Empty {
+                    continue;
+                }
+
+                if target.color == c {
+                    if candidate.is_some() { // two of our own pieces in a row, no pin
+                        break;
+                    }
+                    candidate = Some(index);
+                    continue;
+                }
+
+                let slides_here = self.movement(target.piece).rides.contains(&direction);
+
+                if let (Some(pinned), true) = (candidate, slides_here) {
+                    pins.push((pinned, Ray { king, pinner: index, direction }));
+                }
+                break;
+            }
+        }
+
+        pins
+    }
+
+    // how many squares color c attacks (or defends)
+    pub fn attacked_squares(&self, c: Color) -> usize {
+        self.get_attack_map(c).count_ones() as usize
+    }
+
+    // pseudo-legal move count for color c, whoever is to play
+    pub fn mobility(&self, c: Color) -> usize {
+        let mut moves = MoveList::new();
+        let mut count = 0;
+
+        for (index, square) in self.squares.iter().enumerate() {
+            if square.piece != PieceType::Empty && square.color == c {
+                moves.clear();
+                self.get_moves_single(index, &mut moves);
+                count += moves.len();
+            }
+        }
+
+        count
+    }
+
+    // does moveop check the opponent, directly or by uncovering a slider? The move isn't made.
+    pub fn gives_check(&self, moveop: &MoveOp) -> bool {
+        let mover = self.squares[moveop.from];
+        let enemy_king = match self.get_table_colored(PieceType::King, mover.color.opposite()).next() {
+            Some(king) => king,
+            None => return false,
+        };
+        let king_bit: Bitboard = 1 << enemy_king;
+
+        let mut occupied = (self.occupancy() & !(1 << moveop.from)) | 1 << moveop.to;
+        let mut vacated: Bitboard = 1 << moveop.from;
+
+        if moveop.is_enpassant {
+            let captured = match mover.color {
+                Color::White => moveop.to + self.shape.1,
+                Color::Black => moveop.to - self.shape.1,
+            };
+            occupied &= !(1 << captured);
+        }
+
+        let landed = Square {
+            color: mover.color,
+            piece: if moveop.promote != PieceType::Empty { moveop.promote } else { mover.piece },
+        };
+
+        // the rook hops over the king when castling, same squares as apply_move uses
+        if moveop.is_castle {
+            let (_, rook_from, rook_to) = self.castle_squares(moveop.from, moveop.to > moveop.from);
+            occupied = (occupied & !(1 << rook_from)) | 1 << rook_to;
+            vacated |= 1 << rook_from;
+
+            let rook = Square { color: mover.color, piece: PieceType::Rook };
+            if self.attacks_as(rook, rook_to, occupied) & king_bit != 0 {
+                return true;
+            }
+        }
+
+        // direct check
+        if self.attacks_as(landed, moveop.to, occupied) & king_bit != 0 {
+            return true;
+        }
+
+        // discovered check, any of our other sliders now seeing the king
+        let pieces = |p: PieceType| self.get_table_colored(p, mover.color).fold(0, |bb: Bitboard, index| bb | 1 << index) & !vacated;
+
+        self.movers().any(|p| (self.ride_attacks(enemy_king, self.movement(p).rides, occupied) & pieces(p)) != 0)
+    }
+
+    pub fn classify(&self, moveop: &MoveOp) -> MoveInfo {
+        let captured = if moveop.is_enpassant {
+            PieceType::Pawn
+        } else {
+            self.squares[moveop.to].piece
+        };
+
+        MoveInfo {
+            moveop: *moveop,
+            piece: self.squares[moveop.from].piece,
+            captured,
+            is_capture: captured != PieceType::Empty,
+            is_promotion: moveop.promote != PieceType::Empty,
+            is_double_push: moveop.set_enpassant.0,
+            is_enpassant: moveop.is_enpassant,
+            is_castle: moveop.is_castle,
+            gives_check: self.gives_check(moveop),
+        }
+    }
+
+    pub fn is_in_check(&self, c: Color) -> bool {
+        match self.get_table_colored(PieceType::King, c).next() {
+            Some(kingloc) => self.is_square_attacked(kingloc, c.opposite()),
+            None => false,
+        }
+    }
+
+    pub fn apply_move_nomut(&self, moveop: MoveOp) -> Self {
+        let mut child: Self = self.clone();
+        child.apply_move(moveop);
+
+        child
+    }
+
+    fn get_sliding_moves_single(&self, piece: PieceType, start_index: usize, moves: &mut MoveList) {
+        let start_sq = self.squares[start_index];
+
+        for &(dr, df) in self.movement(piece).rides {
+            let mut index = start_index;
+            while let Some(target_index) = self.offset_index(index, dr, df) {
+                let target = self.squares[target_index];
+
+                if target.piece != PieceType::Empty && target.color == start_sq.color {
+                    break;
+                }
 
-            if self.squares[advance2].piece == PieceType::Empty {
                 moves.push(MoveOp {
                     from: start_index,
-                    to: advance2,
-                    set_enpassant: (true, advance1),
+                    to: target_index,
                     ..Default::default()
                 });
+
+                if target.piece != PieceType::Empty { // capture, ray ends here
+                    break;
+                }
+                index = target_index;
             }
         }
+    }
+    
+    fn get_leaper_moves_single(&self, start_index: usize, offsets: &[(i16, i16)], moves: &mut MoveList) {
+        let start_sq = self.squares[start_index];
 
-        let mut attack_indices: Vec<usize> = Vec::new();
+        for &(dr, df) in offsets {
+            if let Some(target_index) = self.offset_index(start_index, dr, df) {
+                let target = self.squares[target_index];
 
-        if start_index % self.shape.1 != 0 {
-            attack_indices.push(start_index + (direction * self.shape.1 as i16) as usize - 1);
+                if target.piece == PieceType::Empty || target.color != start_sq.color {
+                    moves.push(MoveOp {
+                        from: start_index,
+                        to: target_index,
+                        ..Default::default()
+                    });
+                }
+            }
         }
+    }
 
-        if start_index % self.shape.1 != self.shape.1 - 1 {
-            attack_indices.push(start_index + (direction * self.shape.1 as i16) as usize + 1);
+    // row of c's first rank, counted from the top
+    fn back_rank(&self, c: Color) -> usize {
+        match c {
+            Color::White => self.shape.0 - 1,
+            Color::Black => 0,
         }
+    }
 
-        for index in attack_indices {
-            if self.squares[index].piece != PieceType::Empty && self.squares[index].color != c{
+    // (king to, rook from, rook to) for castling with the king on king_index. the king ends up on the
+    // g or c file, one in from the corner on capablanca boards too, with the rook on its inside
+    fn castle_squares(&self, king_index: usize, kingside: bool) -> (usize, usize, usize) {
+        let width = self.shape.1;
+        let row_start = king_index - king_index % width;
+
+        if kingside {
+            (row_start + width - 2, row_start + width - 1, row_start + width - 3)
+        } else {
+            (row_start + 2, row_start, row_start + 3)
+        }
+    }
+
+    // castling needs the right, the rook still in its corner, nothing in between,
+    // and the king neither in check nor passing over an attacked square
+    fn get_castling_moves(&self, start_index: usize, moves: &mut MoveList) {
+        let c = self.squares[start_index].color;
+        let (kingside, queenside) = match c {
+            Color::White => self.castling.0,
+            Color::Black => self.castling.1,
+        };
+        if start_index != self.back_rank(c) * self.shape.1 + self.shape.1 / 2 {
+            return;
+        }
+
+        for (side, allowed) in [(true, kingside), (false, queenside)] {
+            let (king_to, rook_from, _) = self.castle_squares(start_index, side);
+            if !allowed || self.squares[rook_from] != (Square{piece: PieceType::Rook, color: c}) {
+                continue;
+            }
+
+            let (low, high) = (start_index.min(rook_from), start_index.max(rook_from));
+            let clear = (low + 1..high).all(|i| self.squares[i].piece == PieceType::Empty);
+            let (low, high) = (start_index.min(king_to), start_index.max(king_to));
+            let safe = (low..=high).all(|i| !self.is_square_attacked(i, c.opposite()));
+
+            if clear && safe {
                 moves.push(MoveOp {
                     from: start_index,
-                    to: index,
+                    to: king_to,
+                    is_castle: true,
                     ..Default::default()
                 });
-            } 
+            }
+        }
+    }
+
+    fn get_king_moves_single(&self, start_index: usize, moves: &mut MoveList) {
+        self.get_leaper_moves_single(start_index, &KING_OFFSETS, moves);
+        self.get_castling_moves(start_index, moves);
+    }
+    
+    // a pawn move, or one move per promotion piece if it reaches the last rank
+    fn push_pawn_move(&self, moveop: MoveOp, moves: &mut MoveList) {
+        let last_rank = match self.squares[moveop.from].color {
+            Color::White => 0,
+            Color::Black => self.shape.0 - 1,
+        };
+
+        if moveop.to / self.shape.1 != last_rank {
+            moves.push(moveop);
+            return;
+        }
+
+        let choices = if self.shape.1 >= 10 { &PROMOTIONS[..] } else { &PROMOTIONS[..4] };
+        for &promote in choices {
+            moves.push(MoveOp { promote, ..moveop });
+        }
+    }
+
+    fn get_pawn_moves_single(&self, start_index: usize, c: Color, moves: &mut MoveList) {
+        let (direction, start_rank): (i16, usize) = match c {
+            Color::White => (-1, self.shape.0 - 2),
+            Color::Black => ( 1, 1),
+        };
+
+        if let Some(advance1) = self.offset_index(start_index, direction, 0) {
+            if self.squares[advance1].piece == PieceType::Empty {
+                self.push_pawn_move(MoveOp {
+                    from: start_index,
+                    to: advance1,
+                    ..Default::default()
+                }, moves);
+
+                // minichess variants (Los Alamos, Gardner...) drop the double step along with the ranks
+                if start_index / self.shape.1 == start_rank && self.shape.0 >= 8 {
+                    if let Some(advance2) = self.offset_index(advance1, direction, 0) {
+                        if self.squares[advance2].piece == PieceType::Empty {
+                            moves.push(MoveOp {
+                                from: start_index,
+                                to: advance2,
+                                set_enpassant: (true, advance1),
+                                ..Default::default()
+                            });
+                        }
+                    }
+                }
+            }
+        }
 
-            if self.en_passant.0 && index == self.en_passant.1 {
+        for df in [-1, 1] {
+            let index = match self.offset_index(start_index, direction, df) {
+                Some(index) => index,
+                None => continue,
+            };
+
+            if self.squares[index].piece != PieceType::Empty && self.squares[index].color != c {
+                self.push_pawn_move(MoveOp {
+                    from: start_index,
+                    to: index,
+                    ..Default::default()
+                }, moves);
+            } else if self.en_passant.0 && index == self.en_passant.1 {
                 moves.push(MoveOp{
                     from: start_index,
                     to: index,
@@ -647,45 +1684,268 @@ impl Board {
                 })
             }
         }
-
-        moves
     }
 
-    fn get_pawn_moves(&self)->Vec<MoveOp> {
-        let indices = self.get_table_colored(PieceType::Pawn, self.to_play);
-        let mut moves: Vec<MoveOp> = Vec::new();
-        for start_index in indices {
-            moves.append(&mut self.get_pawn_moves_single(start_index, self.to_play));
+    // pseudo-legal moves for every piece of the side to play, in square order so seeded games repeat
+    fn get_all_moves(&self) -> MoveList {
+        let mut moves = MoveList::new();
+        for start_index in 0..self.squares.len() {
+            if self.squares[start_index].color == self.to_play {
+                self.get_moves_single(start_index, &mut moves);
+            }
         }
 
         moves
     }
 
+    // pseudo-legal moves of whatever piece stands on start_index
+    fn get_moves_single(&self, start_index: usize, moves: &mut MoveList) {
+        let square = self.squares[start_index];
+        match square.piece {
+            PieceType::Empty  => (),
+            PieceType::Pawn   => self.get_pawn_moves_single(start_index, square.color, moves),
+            PieceType::King   => self.get_king_moves_single(start_index, moves),
+            piece => {
+                self.get_sliding_moves_single(piece, start_index, moves);
+                self.get_leaper_moves_single(start_index, self.movement(piece).leaps, moves);
+            },
+        }
+    }
 
-    fn get_all_moves(&self) -> Vec<MoveOp> {
-        let mut moves: Vec<MoveOp> = Vec::new();
-        moves.extend(self.get_king_moves());
-        moves.extend(self.get_sliding_moves(PieceType::Queen));
-        moves.extend(self.get_sliding_moves(PieceType::Bishop));
-        moves.extend(self.get_sliding_moves(PieceType::Rook));
-        moves.extend(self.get_knight_moves());
+    // would moveop leave the mover's king in check? the move is made and taken back
+    // again, the board is left as it was
+    fn leaves_king_in_check(&mut self, moveop: MoveOp) -> bool {
+        let mover = self.to_play;
+        let undo = self.make_move(moveop);
+        let in_check = self.is_in_check(mover);
+        self.unmake_move(undo);
 
-        moves
+        in_check
     }
 
-    fn get_legal_moves(&self) -> Vec<MoveOp> {
-        let candidates = self.get_all_moves();
-        let mut moves: Vec<MoveOp> = Vec::new();
-        for m in &candidates {
-            let newboard = self.apply_move_nomut(*m);
-            let kingloc = newboard.get_table_colored(PieceType::King, self.to_play)[0];
-            if !newboard.get_all_moves().into_iter().map(|m| m.to).any(|i| i == kingloc){
+    // drop the candidates that leave the mover's king in check
+    fn filter_legal(&mut self, candidates: &MoveList) -> MoveList {
+        let mut moves = MoveList::new();
+        for m in candidates {
+            if !self.leaves_king_in_check(*m) {
                 moves.push(*m);
             }
         }
 
         moves
     }
+
+    // every legal move for the side to play. takes &mut self to try each move on the
+    // board itself, which is back as it was by the time this returns
+    pub fn get_legal_moves(&mut self) -> MoveList {
+        let candidates = self.get_all_moves();
+        self.filter_legal(&candidates)
+    }
+
+    pub fn is_legal(&mut self, moveop: &MoveOp) -> Result<(), MoveError> {
+        let board_size = self.shape.0 * self.shape.1;
+        if moveop.from >= board_size || moveop.to >= board_size {
+            return Err(MoveError::OffBoard);
+        }
+
+        if self.result != GameResult::Active {
+            return Err(MoveError::GameOver);
+        }
+
+        let square = self.squares[moveop.from];
+        if square.piece == PieceType::Empty {
+            return Err(MoveError::NoPieceOnFrom);
+        }
+
+        if square.color != self.to_play {
+            return Err(MoveError::WrongColor);
+        }
+
+        let mut candidates = MoveList::new();
+        self.get_moves_single(moveop.from, &mut candidates);
+        if !candidates.contains(moveop) {
+            return Err(MoveError::IllegalPieceMove);
+        }
+
+        if self.leaves_king_in_check(*moveop) {
+            return Err(MoveError::LeavesKingInCheck);
+        }
+
+        Ok(())
+    }
+
+    // checked counterpart to apply_move for moves that didn't come from our own generator
+    pub fn try_move(&mut self, moveop: MoveOp) -> Result<(), MoveError> {
+        self.is_legal(&moveop)?;
+        self.apply_move(moveop);
+        self.update_result();
+
+        Ok(())
+    }
+
+    // repetitions and the 50 move rule don't end the game by themselves, the player to move
+    // has to claim the draw (FIDE 9.2, 9.3). a mate on the move that got there still wins
+    pub fn claimable_draw(&self) -> Option<GameResult> {
+        if self.result != GameResult::Active {
+            None
+        } else if self.repetitions() >= 3 {
+            Some(GameResult::DrawThreefold)
+        } else if self.halfmove_clock >= 100 {
+            Some(GameResult::Draw50Moves)
+        } else {
+            None
+        }
+    }
+
+    // end the game with the draw claimable_draw offers, false if there is none
+    pub fn claim_draw(&mut self) -> bool {
+        match self.claimable_draw() {
+            Some(draw) => {
+                self.result = draw;
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn has_legal_moves(&mut self) -> bool {
+        self.get_all_moves().iter().any(|m| !self.leaves_king_in_check(*m))
+    }
+
+    // flag checkmate/stalemate once the side to move has nothing left to play
+    pub(crate) fn update_result(&mut self) {
+        if self.result != GameResult::Active || self.has_legal_moves() {
+            return;
+        }
+
+        self.result = if !self.is_in_check(self.to_play) {
+            GameResult::DrawStalemate
+        } else {
+            match self.to_play {
+                Color::White => GameResult::BlackCheckmate,
+                Color::Black => GameResult::WhiteCheckmate,
+            }
+        };
+    }
+
+    pub fn termination(&self) -> Option<Termination> {
+        self.result.termination()
+    }
+
+    // Zobrist key of the position: pieces, side to move, castling rights and en passant file.
+    // The move clocks and result aren't part of it.
+    pub fn key(&self) -> u64 {
+        let mut key: u64 = 0;
+
+        for (index, square) in self.squares.iter().enumerate() {
+            if square.piece != PieceType::Empty {
+                key ^= ZOBRIST.piece(square.color, square.piece, index);
+            }
+        }
+
+        if self.to_play == Color::Black {
+            key ^= ZOBRIST.black_to_play;
+        }
+
+        let rights = [self.castling.0.0, self.castling.0.1, self.castling.1.0, self.castling.1.1];
+        for (i, &right) in rights.iter().enumerate() {
+            if right {
+                key ^= ZOBRIST.castling[i];
+            }
+        }
+
+        if self.en_passant.0 {
+            key ^= ZOBRIST.en_passant[self.en_passant.1 % self.shape.1];
+        }
+
+        key
+    }
+
+    // copy of the board with every square (and the en passant target) moved through map
+    fn remap_squares(&self, map: impl Fn(usize) -> usize) -> Board {
+        let mut board = self.clone();
+        for (index, square) in self.squares.iter().enumerate() {
+            board.squares[map(index)] = *square;
+        }
+
+        if self.en_passant.0 {
+            board.en_passant.1 = map(self.en_passant.1);
+        }
+
+        board.history.clear(); // a different game now
+        board.populate_map();
+        board.update_attacks();
+
+        board
+    }
+
+    // castling rights whose king or rook isn't on its start square any more
+    fn drop_lost_castling(&mut self) {
+        let width = self.shape.1;
+        for c in [Color::White, Color::Black] {
+            let row = self.back_rank(c) * width;
+            let on = |index: usize, piece: PieceType| self.squares[index] == Square{piece, color: c};
+            let king = on(row + width / 2, PieceType::King);
+            let (kingside, queenside) = (king && on(row + width - 1, PieceType::Rook), king && on(row, PieceType::Rook));
+
+            let rights = match c {
+                Color::White => &mut self.castling.0,
+                Color::Black => &mut self.castling.1,
+            };
+            rights.0 &= kingside;
+            rights.1 &= queenside;
+        }
+    }
+
+    // a-file <-> h-file, kingside and queenside castling rights trade places. on even
+    // widths the kings leave their start file, so those rights are gone
+    pub fn mirror_horizontal(&self) -> Board {
+        let width = self.shape.1;
+        let mut board = self.remap_squares(|index| index - index % width + (width - 1 - index % width));
+        board.castling = ((self.castling.0.1, self.castling.0.0), (self.castling.1.1, self.castling.1.0));
+        board.drop_lost_castling();
+
+        board
+    }
+
+    // rank 1 <-> rank 8, pieces keep their colors. pawns now head back the way they came,
+    // so there is no en passant, and kings off their own back rank can't castle
+    pub fn flip_vertical(&self) -> Board {
+        let (height, width) = self.shape;
+        let mut board = self.remap_squares(|index| (height - 1 - index / width) * width + index % width);
+        board.en_passant = (false, 0);
+        board.drop_lost_castling();
+
+        board
+    }
+
+    // white pieces become black and vice versa, along with the move and castling rights.
+    // pawn directions turn around here too, same as flip_vertical
+    pub fn swap_colors(&self) -> Board {
+        let mut board = self.clone();
+        for square in board.squares.iter_mut().filter(|s| s.piece != PieceType::Empty) {
+            square.color = square.color.opposite();
+        }
+
+        board.to_play = self.to_play.opposite();
+        board.castling = (self.castling.1, self.castling.0);
+        board.en_passant = (false, 0);
+        board.drop_lost_castling();
+        board.history.clear(); // a different game now
+        board.update_attacks();
+
+        board
+    }
+
+    // legal moves of the piece on start_index, empty if it isn't the side to move's
+    pub fn legal_moves_from(&mut self, start_index: usize) -> MoveList {
+        let mut candidates = MoveList::new();
+        if self.squares[start_index].color == self.to_play {
+            self.get_moves_single(start_index, &mut candidates);
+        }
+
+        self.filter_legal(&candidates)
+    }
 }
 
 impl Default for Board {
@@ -700,10 +1960,34 @@ impl Default for Board {
             halfmove_clock: 0,
             fullmove_number: 0,
             result: GameResult::default(),
+            attacks: (0, 0),
+            attacks_from: Vec::new(),
+            history: Vec::new(),
+            fairies: Arc::default(),
         }
     }
 }
 
+// Boards compare as positions, same fields as key(), and of the same variant
+impl PartialEq for Board {
+    fn eq(&self, other: &Self) -> bool {
+        self.shape == other.shape
+            && (Arc::ptr_eq(&self.fairies, &other.fairies) || self.fairies == other.fairies)
+            && self.squares == other.squares
+            && self.to_play == other.to_play
+            && self.castling == other.castling
+            && self.en_passant == other.en_passant
+    }
+}
+
+impl Eq for Board {}
+
+impl Hash for Board {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.key());
+    }
+}
+
 impl fmt::Display for Board {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", Board::print_board(self))
@@ -733,4 +2017,699 @@ mod tests {
 
         println!("{}", board);
     }
+
+    #[test]
+    fn fen_lenient_test() {
+        let strict = Board::from_fen(START_FEN).unwrap();
+
+        assert!(Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -").is_err());
+        assert!(Board::from_fen_lenient("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -").unwrap() == strict);
+        assert!(Board::from_fen_lenient("  rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR\tw  KQkq - 0   1 \n").unwrap() == strict);
+        assert!(Board::from_fen_lenient("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - bm e4; id \"start\";").unwrap() == strict);
+
+        let board = Board::from_fen_lenient("4k3/8/8/8/8/8/8/4K3").unwrap();
+        assert_eq!(board.to_play, Color::White);
+        assert_eq!(board.castling, ((false, false), (false, false)));
+        assert_eq!((board.halfmove_clock, board.fullmove_number), (0, 1));
+
+        assert!(Board::from_fen_lenient("   ").is_err());
+        assert!(Board::from_fen_lenient("4k3/8/7 w - - 0 1").is_err());
+    }
+
+    #[test]
+    fn null_move_test() {
+        let mut board = Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 3 2").unwrap();
+        let original = board.clone();
+
+        let undo = board.make_null_move();
+        assert_eq!(board.to_play, Color::White);
+        assert_eq!(board.en_passant, (false, 0));
+        assert_eq!((board.halfmove_clock, board.fullmove_number), (4, 3));
+        assert!(board.key() != original.key());
+        assert!(board == Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 4 3").unwrap());
+
+        board.unmake_null_move(undo);
+        assert!(board == original);
+        assert_eq!(board.key(), original.key());
+        assert_eq!((board.halfmove_clock, board.fullmove_number), (3, 2));
+    }
+
+    #[test]
+    fn set_to_play_test() {
+        // white queen gives check, so black must be the one to move
+        let mut board = Board::from_fen("4k3/8/8/8/4Q3/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(board.set_to_play(Color::White), Err(SetupError::OpponentInCheck));
+        assert_eq!(board.to_play, Color::Black);
+        assert_eq!(board.set_to_play(Color::Black), Ok(()));
+
+        // editing squares directly, then handing the move over
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        board.squares[5] = Square{piece: PieceType::Queen, color: Color::White}; // f8
+        board.squares[13] = Square{piece: PieceType::Rook, color: Color::White}; // f7
+        assert_eq!(board.set_to_play(Color::White), Err(SetupError::OpponentInCheck));
+        assert_eq!(board.set_to_play(Color::Black), Ok(()));
+        assert_eq!(board.result, GameResult::WhiteCheckmate);
+
+        board.squares[5] = Square::default();
+        board.squares[13] = Square::default();
+        assert_eq!(board.set_to_play(Color::White), Ok(()));
+        assert_eq!(board.result, GameResult::Active);
+        assert_eq!(board.legal_moves_from(60).len(), 5);
+    }
+
+    #[test]
+    fn odds_test() {
+        assert!(Board::odds_start(Color::Black, Odds::Knight) == Board::from_fen("r1bqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap());
+        assert!(Board::odds_start(Color::Black, Odds::Rook) == Board::from_fen("1nbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQk - 0 1").unwrap());
+        assert!(Board::odds_start(Color::White, Odds::Queen) == Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNB1KBNR w KQkq - 0 1").unwrap());
+
+        let board = Board::odds_start(Color::White, Odds::Rook);
+        assert_eq!(board.castling, ((true, false), (true, true)));
+        assert_eq!(board.get_table(PieceType::Rook).len(), 3);
+    }
+
+    // the position of board under other en passant squares and move clocks, every file on the
+    // two en passant ranks and one that never is. from_fen takes each or refuses it, and
+    // whatever it takes keeps its clocks and can be played on
+    fn fuzz_fen_fields(board: &Board) {
+        let clocks = [(0, 1), (99, 80), (100, 1), (u16::MAX, u16::MAX)];
+        let fen = board.to_fen();
+        let fields = fen.split(' ').collect::<Vec<_>>();
+        let (height, width) = board.shape;
+
+        let squares = [2, height - 3, height / 2].into_iter().flat_map(|row| (0..width).map(move |file| row * width + file));
+        for (i, ep) in std::iter::once("-".to_string()).chain(squares.map(|index| board.index_to_alg(index))).enumerate() {
+            let (halfmove, fullmove) = clocks[i % clocks.len()];
+            let text = format!("{} {} {} {} {} {}", fields[0], fields[1], fields[2], ep, halfmove, fullmove);
+
+            let mut parsed = match Board::from_fen(&text) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    assert_eq!(e, 3, "{}", text);
+                    continue;
+                },
+            };
+            assert_eq!(parsed.to_fen(), text);
+            for m in &parsed.get_legal_moves() {
+                let (before, key) = (parsed.clone(), parsed.key());
+                let undo = parsed.make_move(*m);
+                assert!(parsed.halfmove_clock <= halfmove.saturating_add(1) && parsed.fullmove_number >= fullmove);
+                parsed.unmake_move(undo);
+                assert!(parsed == before && parsed.key() == key && parsed.attacks == before.attacks);
+            }
+        }
+    }
+
+    // random games from a few starts, checking after every ply that the game's SAN and FEN
+    // history replays, that nothing kept incrementally has drifted and that every
+    // reversible operation reverses
+    fn fuzz_playouts(seeds: std::ops::RangeInclusive<u64>, max_plies: usize) {
+        let starts = [START_FEN, "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", CAPABLANCA_FEN];
+        let pieces = [PieceType::Pawn, PieceType::Rook, PieceType::Knight, PieceType::Bishop, PieceType::Queen, PieceType::King, PieceType::Archbishop, PieceType::Chancellor];
+
+        for seed in seeds {
+            let start = crate::game::Game::new(Board::from_fen(starts[seed as usize % starts.len()]).unwrap());
+            let game = start.random_playout(seed, max_plies);
+            assert_eq!(game.plies(), start.random_playout(seed, max_plies).plies());
+
+            let mut board = start.board().clone();
+            for ply in game.plies() {
+                assert_eq!(board.parse_san(&ply.san), Ok(ply.moveop), "{} {}", ply.san, board.to_fen());
+                board.try_move(ply.moveop).unwrap();
+                assert_eq!(board.to_fen(), ply.fen);
+                fuzz_fen_fields(&board);
+
+                // incrementally kept state matches a rebuild from the squares
+                for p in pieces {
+                    let mut table = board.get_table(p).to_vec();
+                    table.sort();
+                    assert_eq!(table, board.search_piece(p));
+                }
+                assert_eq!(board.attacks, (board.compute_attacks(Color::White), board.compute_attacks(Color::Black)));
+
+                // the FEN holds the whole position, clocks included, and hashes the same
+                let fen = board.to_fen();
+                let parsed = Board::from_fen(&fen).unwrap();
+                assert!(parsed == board && parsed.key() == board.key());
+                assert_eq!((parsed.halfmove_clock, parsed.fullmove_number), (board.halfmove_clock, board.fullmove_number));
+                assert_eq!(parsed.to_fen(), fen);
+
+                // reversible operations come back to the same position and key
+                let (before, key) = (board.clone(), board.key());
+                let undo = board.make_null_move();
+                assert!(board.key() != key);
+                board.unmake_null_move(undo);
+                assert!(board == before && board.key() == key);
+                assert!(board.swap_colors().swap_colors().squares == board.squares);
+                assert!(board.mirror_horizontal().mirror_horizontal().squares == board.squares);
+                assert_eq!(board.mirror_horizontal().validate(), Ok(()));
+                assert_eq!(board.flip_vertical().swap_colors().validate(), Ok(()));
+
+                for legal in &board.get_legal_moves() {
+                    assert_eq!(board.is_legal(legal), Ok(()));
+
+                    let undo = board.make_move(*legal);
+                    board.unmake_move(undo);
+                    assert!(board == before && board.key() == key && board.attacks == before.attacks && board.result == before.result);
+                    assert_eq!((board.halfmove_clock, board.fullmove_number), (before.halfmove_clock, before.fullmove_number));
+                    assert_eq!(board.history, before.history);
+                }
+                for p in pieces {
+                    let mut table = board.get_table(p).to_vec();
+                    table.sort();
+                    assert_eq!(table, board.search_piece(p));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn random_playout_fuzz_test() {
+        fuzz_playouts(1..=12, 120);
+    }
+
+    // the long run, for after touching move generation: cargo test --release -- --ignored
+    #[test]
+    #[ignore]
+    fn random_playout_soak_test() {
+        fuzz_playouts(1..=10_000, 300);
+    }
+
+    #[test]
+    fn validate_test() {
+        let check = |fen| Board::from_fen(fen).unwrap().validate();
+
+        assert_eq!(check(START_FEN), Ok(()));
+        assert_eq!(check("rnbqkbnr/pppp1ppp/8/8/3Pp3/8/PPP1PPPP/RNBQKBNR b KQkq d3 0 3"), Ok(()));
+        assert_eq!(check("4k3/8/8/8/8/8/8/8 w - - 0 1"), Err(SetupError::MissingKing(Color::White)));
+        assert_eq!(check("4k3/8/8/8/8/8/8/3KK3 w - - 0 1"), Err(SetupError::ExtraKing(Color::White)));
+        assert_eq!(check("4k3/8/8/8/4R3/8/8/4K3 w - - 0 1"), Err(SetupError::OpponentInCheck));
+        assert_eq!(check("4k2P/8/8/8/8/8/8/4K3 w - - 0 1"), Err(SetupError::PawnOnBackRank(7)));
+        assert_eq!(check("4k3/8/8/8/3P4/8/3P4/4K3 b - d3 0 1"), Err(SetupError::BadEnPassant));
+        assert_eq!(check("4k3/8/8/8/3P4/8/8/4K3 b - d3 0 1"), Ok(()));
+        assert_eq!(check("r3k3/8/8/8/8/8/8/R3K3 w Qk - 0 1"), Err(SetupError::BadCastling));
+        assert_eq!(check("r3k3/8/8/8/8/8/8/R3K3 w Qq - 0 1"), Ok(()));
+        assert_eq!(check("r3k3/8/8/8/8/8/8/R2K4 w Q - 0 1"), Err(SetupError::BadCastling));
+    }
+
+    #[test]
+    fn fen_en_passant_test() {
+        // en passant squares with no pawn to take used to panic in from_fen, which already
+        // generates moves to see if the game is over
+        for fen in [
+            "4k3/8/8/4P3/8/8/8/4K3 w - d6 0 1",  // nothing on d5
+            "4k3/8/8/3nP3/8/8/8/4K3 w - d6 0 1", // a knight, not a pawn
+            "4k3/8/8/8/3P4/8/8/4K3 b - d4 0 1",  // the pawn's own square
+            "4k3/8/8/8/3P4/8/8/4K3 w - d3 0 1",  // white can't take its own pawn
+            "4k3/8/8/8/3P4/3P4/8/4K3 b - d3 0 1", // occupied
+            "4k3/8/8/8/3p4/8/8/4K3 w - d5 0 1",   // a rank too far
+        ] {
+            assert_eq!(Board::from_fen(fen).err(), Some(3), "{}", fen);
+        }
+
+        let mut board = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        assert!(board.get_legal_moves().iter().any(|m| m.is_enpassant));
+    }
+
+    #[test]
+    fn board_shape_test() {
+        // Los Alamos: 6x6, no bishops, no pawn double step
+        let mut board = Board::from_fen("rnqknr/pppppp/6/6/PPPPPP/RNQKNR w - - 0 1").unwrap();
+        assert_eq!(board.shape, (6, 6));
+        assert_eq!(board.squares.len(), 36);
+        assert_eq!(board.index_to_alg(0), "a6");
+        assert_eq!(board.get_legal_moves().len(), 10);
+        assert_eq!(board.validate(), Ok(()));
+
+        // multi-digit runs of empty squares on a 10 file board
+        let mut board = Board::from_fen("4k5/10/10/10/10/10/4P5/4K5 w - - 0 1").unwrap();
+        assert_eq!(board.shape, (8, 10));
+        assert_eq!(board.index_to_alg(79), "j1");
+        assert_eq!(board.legal_moves_from(64).len(), 2);
+
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/4K3/8/8/8/8/8/8/8/8/8 w - - 0 1").err(), Some(2));
+        assert_eq!(Board::from_fen("4k3/9/8/8/8/8/8/4K3 w - - 0 1").err(), Some(2));
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - e9 0 1").err(), Some(3));
+
+        // too small for pawn moves, and the widest files still have en passant keys
+        assert_eq!(Board::from_fen("KPk w - - 0 1").err(), Some(2));
+        assert_eq!(Board::from_fen("k2/3/3/K2 w - - 0 1").err(), Some(2));
+        let mut board = Board::from_fen("k19/20/20/16p3/20/K19 w - q4 0 1").unwrap();
+        assert_eq!(board.shape, (6, 20));
+        assert_ne!(board.key(), Board::from_fen("k19/20/20/16p3/20/K19 w - - 0 1").unwrap().key());
+        let m = board.get_legal_moves()[0];
+        assert!(board.try_move(m).is_ok());
+    }
+
+    #[test]
+    fn capablanca_test() {
+        let mut board = Board::from_fen(CAPABLANCA_FEN).unwrap();
+        assert_eq!(board.shape, (8, 10));
+        assert_eq!(board.get_legal_moves().len(), 28);
+        assert_eq!(board.validate(), Ok(()));
+
+        // archbishop on d4: bishop lines plus knight jumps
+        let mut board = Board::from_fen("4k5/10/10/10/3A6/10/10/4K5 w - - 0 1").unwrap();
+        assert_eq!(board.legal_moves_from(43).len(), 13 + 8);
+        assert!(board.gives_check(&MoveOp{from: 43, to: 25, ..Default::default()})); // Af6+, knight jump onto e8
+
+        // chancellor on e5 pins along the file and checks like a knight
+        let mut board = Board::from_fen("4k5/10/4n5/4C5/10/10/10/4K5 b - - 0 1").unwrap();
+        assert_eq!(board.pinned(Color::Black).len(), 1);
+        assert_eq!(board.legal_moves_from(24).len(), 0);
+        assert_eq!(board.attackers_of(4, Color::White), 0);
+        assert_eq!(board.attackers_of(13, Color::White).count_ones(), 1); // d7 by knight jump
+    }
+
+    #[test]
+    fn movement_test() {
+        assert_eq!(PieceType::Pawn.movement(), Movement{leaps: &[], rides: &[]});
+        assert_eq!(PieceType::Archbishop.movement(), Movement{leaps: &KNIGHT_OFFSETS, rides: &BISHOP_DIRS});
+
+        // every move generated from a descriptor is also in the attack map built from it
+        let mut board = Board::from_fen("r1a1k1c2r/10/10/4Q5/10/2N7/10/R3K4R w - - 0 1").unwrap();
+        let occupied = board.occupancy();
+        for index in 0..board.squares.len() {
+            let square = board.squares[index];
+            if square.piece == PieceType::Empty || square.color != Color::White {
+                continue;
+            }
+            let attacks = board.movement_attacks(index, board.movement(square.piece), occupied);
+            for m in &board.legal_moves_from(index) {
+                assert!(attacks & (1 << m.to) != 0);
+            }
+            assert_eq!(board.piece_attacks(index, occupied), attacks);
+        }
+
+        // a direction outside the rook/bishop lines goes through the plain ray walk
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/N3K3 w - - 0 1").unwrap();
+        let nightrider = board.ride_attacks(56, &KNIGHT_OFFSETS, board.occupancy());
+        assert_eq!(bitboard_squares(nightrider).map(|i| board.index_to_alg(i)).collect::<Vec<_>>(), ["d7", "c5", "g4", "b3", "e3", "c2"].map(String::from));
+    }
+
+    #[test]
+    fn movelist_test() {
+        let mut moves = MoveList::new();
+        assert!(moves.is_empty());
+
+        moves.push(MoveOp{from: 52, to: 36, ..Default::default()});
+        moves.push(MoveOp{from: 12, to: 28, ..Default::default()});
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves.iter().map(|m| m.to).collect::<Vec<_>>(), vec![36, 28]);
+
+        moves.clear();
+        assert!(moves.is_empty());
+
+        let board = Board::from_fen(START_FEN).unwrap();
+        let generated = board.get_all_moves();
+        assert!(generated.iter().all(|m| board.squares[m.from].color == Color::White));
+
+        // more moves than fit inline spill over instead of running off the end
+        let mut board = Board::from_fen("k2C6/1Q2Q4Q/Q3C2C1Q/4Q1C3/5Q3Q/CC8/2CQ1C2Q1/QC4C1QK w - - 0 1").unwrap();
+        let generated = board.get_all_moves();
+        assert!(generated.len() > MAX_MOVES);
+        let chancellor = board.legal_moves_from(3);
+        assert!(!chancellor.is_empty() && chancellor.iter().all(|m| generated.contains(m)));
+    }
+
+    #[test]
+    fn attack_map_test() {
+        let board = Board::from_fen(START_FEN).unwrap();
+        // white covers its whole third rank, black its sixth
+        assert_eq!(board.get_attack_map(Color::White) & (0xff << 40), 0xff << 40);
+        assert_eq!(board.get_attack_map(Color::Black) & (0xff << 16), 0xff << 16);
+        assert_eq!(board.get_attack_map(Color::White) & 0xffff_ffff, 0);
+
+        // fool's mate, the queen on h4 checks along the diagonal
+        let mut board = Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        assert!(board.is_in_check(Color::White));
+        assert!(!board.is_in_check(Color::Black));
+        assert!(board.get_legal_moves().is_empty());
+
+        // pawns take diagonally, the square in front isn't attacked
+        let board = Board::from_fen("4k3/8/8/8/4P3/8/8/K7 w - - 0 1").unwrap();
+        let e4 = board.parse_square("e4").unwrap();
+        for (name, attacked) in [("d5", true), ("f5", true), ("e5", false), ("d3", false)] {
+            assert_eq!(board.is_square_attacked(board.parse_square(name).unwrap(), Color::White), attacked);
+        }
+        assert!(!board.is_square_attacked(e4, Color::White));
+        let white: Vec<String> = board.attack_set(Color::White).map(|i| board.index_to_alg(i)).collect();
+        assert_eq!(white, ["d5", "f5", "a2", "b2", "b1"]);
+        assert_eq!(board.attack_set(Color::Black).count(), board.attacked_squares(Color::Black));
+    }
+
+    #[test]
+    fn attackers_of_test() {
+        // e5 pawn: hit by the g7 bishop, c6 knight, d6 pawn and h5 rook;
+        // defended by the d4 pawn and the e1 rook
+        let board = Board::from_fen("4k3/6b1/2np4/4P2r/3P4/8/8/4RK2 w - - 0 1").unwrap();
+        let e5 = 28;
+
+        assert_eq!(bitboard_squares(board.attackers_of(e5, Color::Black)).collect::<Vec<_>>(), vec![14, 18, 19, 31]);
+        assert_eq!(bitboard_squares(board.attackers_of(e5, Color::White)).collect::<Vec<_>>(), vec![35, 60]);
+        assert_eq!(board.attackers_of(0, Color::White), 0);
+    }
+
+    #[test]
+    fn pinned_test() {
+        // e2 knight pinned by the e8 rook, c3 bishop pinned by the a5 queen,
+        // g3 pawn shielded by the f2 rook is not pinned by the h4 bishop
+        let board = Board::from_fen("4r1k1/8/8/q7/7b/2B3P1/4NR2/4K3 w - - 0 1").unwrap();
+        let pins = board.pinned(Color::White);
+
+        assert_eq!(pins, vec![
+            (52, Ray { king: 60, pinner: 4, direction: (-1, 0) }),
+            (42, Ray { king: 60, pinner: 24, direction: (-1, -1) }),
+        ]);
+        assert!(board.pinned(Color::Black).is_empty());
+    }
+
+    #[test]
+    fn gives_check_test() {
+        let mv = |from, to| MoveOp{from, to, ..Default::default()};
+
+        // knight on e4 screens the e1 rook from the e8 king
+        let board = Board::from_fen("4k3/8/8/8/4N3/8/8/4RK2 w - - 0 1").unwrap();
+        assert!(board.gives_check(&mv(36, 21)));  // Nf6+, direct and discovered
+        assert!(board.gives_check(&mv(36, 26)));  // Nc5+, discovered
+        assert!(!board.gives_check(&mv(60, 52))); // Re2
+        assert!(!board.gives_check(&mv(61, 54))); // Kg2
+
+        let board = Board::from_fen("4k3/8/8/8/8/3B4/8/4K3 w - - 0 1").unwrap();
+        assert!(board.gives_check(&mv(43, 25)));  // Bb5+
+        assert!(!board.gives_check(&mv(43, 34))); // Bc4
+
+        // exd6 e.p. removes the d5 pawn from the b3-g8 diagonal
+        let board = Board::from_fen("6k1/8/8/3pP3/8/1B6/8/K7 w - d6 0 1").unwrap();
+        assert!(board.gives_check(&MoveOp{from: 28, to: 19, is_enpassant: true, ..Default::default()}));
+        assert!(!board.gives_check(&mv(41, 34)));
+
+        let board = Board::from_fen("7k/P7/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        assert!(board.gives_check(&MoveOp{from: 8, to: 0, promote: PieceType::Queen, ..Default::default()}));
+        assert!(!board.gives_check(&MoveOp{from: 8, to: 0, promote: PieceType::Knight, ..Default::default()}));
+
+        // the castled rook lands on f1 facing the f8 king
+        let board = Board::from_fen("5k2/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert!(board.gives_check(&MoveOp{from: 60, to: 62, is_castle: true, ..Default::default()}));
+    }
+
+    #[test]
+    fn classify_test() {
+        let board = Board::from_fen("4k3/8/3p4/4P3/8/8/3P4/4K3 w - - 0 1").unwrap();
+
+        let capture = board.classify(&MoveOp{from: 28, to: 19, ..Default::default()});
+        assert_eq!((capture.piece, capture.captured, capture.is_capture), (PieceType::Pawn, PieceType::Pawn, true));
+        assert!(!capture.gives_check && !capture.is_promotion);
+
+        let push = board.classify(&MoveOp{from: 51, to: 35, set_enpassant: (true, 43), ..Default::default()});
+        assert!(push.is_double_push && !push.is_capture);
+
+        let board = Board::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let promotion = board.classify(&MoveOp{from: 8, to: 0, promote: PieceType::Queen, ..Default::default()});
+        assert!(promotion.is_promotion && promotion.gives_check);
+    }
+
+    #[test]
+    fn mobility_test() {
+        let board = Board::from_fen(START_FEN).unwrap();
+        assert_eq!(board.mobility(Color::White), 20);
+        assert_eq!(board.mobility(Color::Black), 20);
+        assert_eq!(board.attacked_squares(Color::White), 22);
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 b - - 0 1").unwrap();
+        assert_eq!(board.mobility(Color::White), 10 + 5);
+        assert_eq!(board.attacked_squares(Color::White), 15);
+    }
+
+    #[test]
+    fn legal_moves_from_test() {
+        let mut board = Board::from_fen(START_FEN).unwrap();
+        let targets = |b: &mut Board, index: usize| b.legal_moves_from(index).iter().map(|m| m.to).collect::<Vec<_>>();
+
+        assert_eq!(targets(&mut board, 57), vec![40, 42]); // Nb1: a3, c3
+        assert_eq!(targets(&mut board, 52), vec![44, 36]); // e2: e3, e4
+        assert!(targets(&mut board, 59).is_empty()); // boxed in queen
+        assert!(targets(&mut board, 12).is_empty()); // black isn't to play
+        assert!(targets(&mut board, 36).is_empty()); // empty square
+
+        // the e2 knight is pinned by the rook on e8, the bishop slides freely
+        let mut board = Board::from_fen("4r1k1/8/8/8/8/8/4N3/2B1K3 w - - 0 1").unwrap();
+        assert!(targets(&mut board, 52).is_empty());
+        assert_eq!(targets(&mut board, 58), vec![51, 44, 37, 30, 23, 49, 40]);
+    }
+
+    #[test]
+    fn is_legal_test() {
+        let mut board = Board::from_fen("4r1k1/8/8/8/8/8/4N3/2B1K3 w - - 0 1").unwrap();
+
+        assert_eq!(board.is_legal(&MoveOp{from: 58, to: 30, ..Default::default()}), Ok(()));
+        assert_eq!(board.is_legal(&MoveOp{from: 58, to: 64, ..Default::default()}), Err(MoveError::OffBoard));
+        assert_eq!(board.is_legal(&MoveOp{from: 57, to: 49, ..Default::default()}), Err(MoveError::NoPieceOnFrom));
+        assert_eq!(board.is_legal(&MoveOp{from: 4, to: 12, ..Default::default()}), Err(MoveError::WrongColor));
+        assert_eq!(board.is_legal(&MoveOp{from: 58, to: 50, ..Default::default()}), Err(MoveError::IllegalPieceMove));
+        assert_eq!(board.is_legal(&MoveOp{from: 52, to: 37, ..Default::default()}), Err(MoveError::LeavesKingInCheck));
+
+        assert_eq!(board.try_move(MoveOp{from: 60, to: 59, ..Default::default()}), Ok(()));
+        assert!(board.to_play == Color::Black);
+    }
+
+    #[test]
+    fn termination_test() {
+        let board = Board::from_fen(START_FEN).unwrap();
+        assert_eq!(board.termination(), None);
+
+        let board = Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        assert_eq!(board.termination(), Some(Termination { outcome: Outcome::Winner(Color::Black), reason: TerminationReason::Checkmate }));
+
+        // black king boxed in on h8 by the queen, not in check
+        let mut board = Board::from_fen("7k/8/5Q2/8/8/8/8/K7 w - - 0 1").unwrap();
+        board.try_move(MoveOp{from: 21, to: 22, ..Default::default()}).unwrap();
+        assert_eq!(board.result, GameResult::DrawStalemate);
+        assert_eq!(board.termination().unwrap().to_string(), "Draw by stalemate");
+    }
+
+    #[test]
+    fn transform_test() {
+        let board = Board::from_fen("r3k2r/ppp1qppp/2n5/3pP3/8/8/PPP2PPP/R3K2R w Kq d6 0 1").unwrap();
+
+        let mirrored = board.mirror_horizontal();
+        assert_eq!(mirrored.squares[59], board.squares[60]); // king e1 -> d1
+        assert_eq!(mirrored.en_passant, (true, 20)); // d6 -> e6
+        assert_eq!(mirrored.validate(), Ok(()));
+        assert!(mirrored.mirror_horizontal().squares == board.squares);
+
+        // flipping and swapping colors gives black the same pieces white had
+        let swapped = board.flip_vertical().swap_colors();
+        assert_eq!(swapped.to_play, Color::Black);
+        assert_eq!(swapped.squares[4], Square { color: Color::Black, piece: PieceType::King });
+        assert_eq!(swapped.squares[36], Square { color: Color::Black, piece: PieceType::Pawn }); // e5 -> e4
+        assert_eq!(swapped.validate(), Ok(()));
+        assert!(swapped.flip_vertical().swap_colors().squares == board.squares);
+
+        // every transform leaves only rights the new position can use
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        for transformed in [board.mirror_horizontal(), board.flip_vertical(), board.swap_colors()] {
+            assert_eq!(transformed.validate(), Ok(()));
+        }
+        let odd = Board::from_fen("r3k3r/9/9/9/9/9/9/9/R3K3R w Kq - 0 1").unwrap();
+        assert_eq!(odd.mirror_horizontal().castling, ((false, true), (true, false)));
+    }
+
+    #[test]
+    fn key_test() {
+        let board = Board::from_fen(START_FEN).unwrap();
+
+        // same position via two move orders
+        let a = board.apply_move_nomut(MoveOp{from: 62, to: 45, ..Default::default()})
+                     .apply_move_nomut(MoveOp{from: 6, to: 21, ..Default::default()})
+                     .apply_move_nomut(MoveOp{from: 57, to: 42, ..Default::default()});
+        let b = board.apply_move_nomut(MoveOp{from: 57, to: 42, ..Default::default()})
+                     .apply_move_nomut(MoveOp{from: 6, to: 21, ..Default::default()})
+                     .apply_move_nomut(MoveOp{from: 62, to: 45, ..Default::default()});
+        assert_eq!(a.key(), b.key());
+        assert!(a == b);
+        assert_ne!(a.key(), board.key());
+
+        let mut other_side = board.clone();
+        other_side.to_play = Color::Black;
+        assert_ne!(other_side.key(), board.key());
+
+        let seen: std::collections::HashSet<Board> = [a, b, board].into_iter().collect();
+        assert_eq!(seen.len(), 2);
+    }
+
+    #[test]
+    fn pgn_result_test() {
+        assert_eq!(GameResult::Active.pgn_result(), "*");
+        assert_eq!(GameResult::Active.pgn_termination(), "unterminated");
+        assert_eq!(GameResult::WhiteCheckmate.pgn_result(), "1-0");
+        assert_eq!(GameResult::BlackTime.pgn_result(), "0-1");
+        assert_eq!(GameResult::BlackTime.pgn_termination(), "time forfeit");
+        assert_eq!(GameResult::DrawStalemate.pgn_result(), "1/2-1/2");
+        assert_eq!(GameResult::DrawStalemate.pgn_termination(), "normal");
+
+        assert_eq!(GameResult::from_pgn("0-1", Some("time forfeit")), Some(GameResult::BlackTime));
+        assert_eq!(GameResult::from_pgn("1-0", None), Some(GameResult::WhiteResign));
+        assert_eq!(GameResult::from_pgn("1/2-1/2", Some("normal")), Some(GameResult::DrawAgreement));
+        assert_eq!(GameResult::from_pgn("2-0", None), None);
+    }
+
+    #[test]
+    fn promotion_test() {
+        // b7 can push to b8 or take on a8, four pieces each
+        let mut board = Board::from_fen("r6k/1P6/8/8/8/8/8/2K5 w - - 0 1").unwrap();
+        let moves = board.legal_moves_from(9);
+        assert_eq!(moves.len(), 8);
+        assert!(moves.iter().all(|m| m.promote != PieceType::Empty));
+
+        let mut after = board.clone();
+        after.try_move(MoveOp{from: 9, to: 0, promote: PieceType::Knight, ..Default::default()}).unwrap();
+        assert!(after == Board::from_fen("N6k/8/8/8/8/8/8/2K5 b - - 0 1").unwrap());
+        assert_eq!(after.get_table(PieceType::Pawn).len(), 0);
+        assert_eq!(after.get_table(PieceType::Knight), &vec![0]);
+        assert_eq!(after.get_table(PieceType::Rook).len(), 0);
+
+        // the bare push is no longer a move
+        assert_eq!(board.is_legal(&MoveOp{from: 9, to: 1, ..Default::default()}), Err(MoveError::IllegalPieceMove));
+
+        // black promotes on the first rank, capablanca pawns can also become an archbishop or chancellor
+        let mut board = Board::from_fen("k9/10/10/10/10/10/5p4/K9 b - - 0 1").unwrap();
+        assert_eq!(board.legal_moves_from(65).len(), 6);
+    }
+
+    #[test]
+    fn castling_test() {
+        let mut board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let castles: Vec<usize> = board.legal_moves_from(60).iter().filter(|m| m.is_castle).map(|m| m.to).collect();
+        assert_eq!(castles, vec![62, 58]);
+
+        let mut after = board.clone();
+        after.try_move(MoveOp{from: 60, to: 62, is_castle: true, ..Default::default()}).unwrap();
+        assert!(after == Board::from_fen("r3k2r/8/8/8/8/8/8/R4RK1 b kq - 1 1").unwrap());
+        assert_eq!(after.get_table(PieceType::Rook).len(), 4);
+        after.try_move(MoveOp{from: 4, to: 2, is_castle: true, ..Default::default()}).unwrap();
+        assert!(after == Board::from_fen("2kr3r/8/8/8/8/8/8/R4RK1 w - - 2 2").unwrap());
+
+        // no castling out of check, through an attacked square, or past a piece
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let no_castles = |fen: &str| Board::from_fen(fen).unwrap().legal_moves_from(60).iter().all(|m| !m.is_castle);
+        assert!(no_castles("4k3/8/8/8/8/8/8/R3K2R w - - 0 1"));
+        assert!(no_castles("4k3/8/8/8/8/8/4r3/R3K2R w KQ - 0 1"));
+        assert!(no_castles("4k3/8/8/8/8/8/3r1r2/R3K2R w KQ - 0 1"));
+        assert!(no_castles("4k3/8/8/8/8/8/8/RN2K1NR w KQ - 0 1"));
+
+        // the b file may be attacked, only the king's path matters
+        let mut board_b = Board::from_fen("1r2k3/8/8/8/8/8/8/R3K3 w Q - 0 1").unwrap();
+        assert!(board_b.legal_moves_from(60).iter().any(|m| m.is_castle));
+
+        // taking a rook in its corner takes the right with it
+        let mut after = board.clone();
+        after.try_move(MoveOp{from: 63, to: 7, ..Default::default()}).unwrap();
+        assert_eq!(after.castling, ((false, true), (false, true)));
+
+        // capablanca: the king goes three squares, to i1 or c1
+        let mut board = Board::from_fen("r4k3r/10/10/10/10/10/10/R4K3R w KQkq - 0 1").unwrap();
+        let castles: Vec<usize> = board.legal_moves_from(75).iter().filter(|m| m.is_castle).map(|m| m.to).collect();
+        assert_eq!(castles, vec![78, 72]);
+        let mut after = board.clone();
+        after.try_move(MoveOp{from: 75, to: 78, is_castle: true, ..Default::default()}).unwrap();
+        assert!(after == Board::from_fen("r4k3r/10/10/10/10/10/10/R6RK1 b kq - 1 1").unwrap());
+    }
+
+    #[test]
+    fn all_moves_test() {
+        let mut board = Board::from_fen(START_FEN).unwrap();
+        assert_eq!(board.get_all_moves().len(), 20);
+        assert_eq!(board.get_legal_moves().len(), 20);
+
+        // only a pawn can block the check, king boxed in by its own pieces
+        let mut board = Board::from_fen("4k3/8/8/b7/8/8/2P1PP2/3BKB2 w - - 0 1").unwrap();
+        let moves = board.get_legal_moves();
+        assert_eq!(moves.iter().map(|m| (m.from, m.to)).collect::<Vec<_>>(), vec![(50, 42)]);
+    }
+
+    #[test]
+    fn threefold_test() {
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/4K1N1 w - - 0 1").unwrap();
+        let shuffle = [(62, 45), (4, 5), (45, 62), (5, 4)]; // Nf3 Kf8 Ng1 Ke8
+
+        let mut counts = Vec::new();
+        for &(from, to) in shuffle.iter().cycle().take(8) {
+            assert_eq!(board.termination(), None);
+            board.try_move(MoveOp{from, to, ..Default::default()}).unwrap();
+            counts.push(board.repetitions());
+        }
+        assert_eq!(counts, vec![1, 1, 1, 2, 2, 2, 2, 3]);
+
+        // the draw is there to claim, the game doesn't end by itself
+        assert_eq!(board.result, GameResult::Active);
+        assert_eq!(board.claimable_draw(), Some(GameResult::DrawThreefold));
+        assert!(board.claim_draw());
+        assert_eq!(board.termination().map(|t| t.reason), Some(TerminationReason::Threefold));
+        assert!(!board.claim_draw());
+
+        // a pawn move wipes the slate
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/P7/4K1N1 w - - 0 1").unwrap();
+        for &(from, to) in &shuffle {
+            board.try_move(MoveOp{from, to, ..Default::default()}).unwrap();
+        }
+        assert_eq!(board.repetitions(), 2);
+        board.try_move(MoveOp{from: 48, to: 40, ..Default::default()}).unwrap();
+        assert_eq!(board.repetitions(), 1);
+    }
+
+    #[test]
+    fn fifty_move_test() {
+        // mate on the hundredth half move wins, the draw was never there to claim
+        let mut board = Board::from_fen("7k/8/6K1/8/8/8/8/R7 w - - 99 80").unwrap();
+        board.try_move(MoveOp{from: 56, to: 0, ..Default::default()}).unwrap(); // Ra8#
+        assert_eq!(board.result, GameResult::WhiteCheckmate);
+        assert_eq!(board.claimable_draw(), None);
+
+        // any other move, and black may claim or play on
+        let mut board = Board::from_fen("7k/8/6K1/8/8/8/8/R7 w - - 99 80").unwrap();
+        board.try_move(MoveOp{from: 56, to: 48, ..Default::default()}).unwrap(); // Ra2
+        assert_eq!(board.claimable_draw(), Some(GameResult::Draw50Moves));
+        assert_eq!(board.clone().try_move(MoveOp{from: 7, to: 6, ..Default::default()}), Ok(())); // Kg8
+        assert!(board.claim_draw());
+        assert_eq!(board.result, GameResult::Draw50Moves);
+        assert_eq!(board.is_legal(&MoveOp{from: 7, to: 6, ..Default::default()}), Err(MoveError::GameOver));
+    }
+
+    #[test]
+    fn to_fen_test() {
+        for fen in [
+            START_FEN,
+            CAPABLANCA_FEN,
+            "r3k2r/8/8/3pP3/8/8/8/R3K2R w Kq d6 12 40",
+            "4k5/10/10/10/10/10/10/4K5 b - - 99 120",
+        ] {
+            assert_eq!(Board::from_fen(fen).unwrap().to_fen(), fen);
+        }
+
+        let mut board = Board::from_fen(START_FEN).unwrap();
+        board.try_move(MoveOp{from: 52, to: 36, set_enpassant: (true, 44), ..Default::default()}).unwrap();
+        board.try_move(MoveOp{from: 6, to: 21, ..Default::default()}).unwrap();
+        assert_eq!(board.to_fen(), "rnbqkb1r/pppppppp/5n2/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 1 2");
+
+        // clocks past nine used to be cut to their first digit
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 37 105").unwrap().fullmove_number, 105);
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 99999").is_err());
+
+        // the counters stop at the top of their range rather than overflowing
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w - - 65535 1").unwrap();
+        board.try_move(MoveOp{from: 63, to: 62, ..Default::default()}).unwrap();
+        assert_eq!(board.halfmove_clock, 65535);
+        let undo = board.make_null_move();
+        assert_eq!(board.halfmove_clock, 65535);
+        board.unmake_null_move(undo);
+
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R b - - 0 65535").unwrap();
+        let undo = board.make_null_move();
+        assert_eq!(board.fullmove_number, 65535);
+        board.unmake_null_move(undo);
+        assert_eq!(board.to_play, Color::Black);
+        board.try_move(MoveOp{from: 4, to: 3, ..Default::default()}).unwrap();
+        assert_eq!(board.to_fen(), "3k4/8/8/8/8/8/8/4K2R w - - 1 65535");
+    }
 }
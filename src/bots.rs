@@ -0,0 +1,245 @@
+// Simple opponents that don't search: they pick straight from the legal move list.
+use crate::board::{Board, Color, MoveOp, Outcome, PieceType};
+use crate::eval::{material, piece_value, value_on, Evaluator};
+
+pub trait Bot {
+    fn name(&self) -> String;
+
+    // None when there is nothing legal to play
+    fn choose_move(&mut self, board: &Board) -> Option<MoveOp>;
+}
+
+// xorshift64, plenty for picking among a few dozen moves
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed.max(1)) // xorshift never leaves zero
+    }
+
+    pub fn from_time() -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Rng::new(nanos)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    pub fn pick<T: Copy>(&mut self, items: &[T]) -> Option<T> {
+        if items.is_empty() {
+            None
+        } else {
+            Some(items[self.below(items.len())])
+        }
+    }
+}
+
+// all the moves that share the best score, so ties can be broken at random
+fn best_moves(board: &Board, score: impl Fn(&MoveOp) -> i32) -> Vec<MoveOp> {
+    let moves = board.clone().get_legal_moves();
+    let best = match moves.iter().map(&score).max() {
+        Some(best) => best,
+        None => return Vec::new(),
+    };
+
+    moves.iter().filter(|m| score(m) == best).copied().collect()
+}
+
+pub struct RandomMover {
+    rng: Rng,
+}
+
+impl RandomMover {
+    pub fn new(rng: Rng) -> Self {
+        RandomMover { rng }
+    }
+}
+
+impl Bot for RandomMover {
+    fn name(&self) -> String {
+        "Random mover".to_string()
+    }
+
+    fn choose_move(&mut self, board: &Board) -> Option<MoveOp> {
+        self.rng.pick(&board.clone().get_legal_moves())
+    }
+}
+
+// takes the most valuable piece it can, otherwise plays at random
+pub struct GreedyCapture {
+    rng: Rng,
+}
+
+impl GreedyCapture {
+    pub fn new(rng: Rng) -> Self {
+        GreedyCapture { rng }
+    }
+}
+
+impl Bot for GreedyCapture {
+    fn name(&self) -> String {
+        "Greedy capture".to_string()
+    }
+
+    fn choose_move(&mut self, board: &Board) -> Option<MoveOp> {
+        let moves = best_moves(board, |m| value_on(board, board.classify(m).captured));
+        self.rng.pick(&moves)
+    }
+}
+
+// looks one ply ahead: mate if it can, otherwise whatever the evaluator likes best. a move
+// that ends the game drawn, or lets the opponent claim a draw, is worth 0 whatever the material
+pub struct OnePlyBot<E: Evaluator> {
+    evaluator: E,
+    rng: Rng,
+}
+
+impl<E: Evaluator> OnePlyBot<E> {
+    pub fn new(evaluator: E, rng: Rng) -> Self {
+        OnePlyBot { evaluator, rng }
+    }
+}
+
+impl<E: Evaluator> Bot for OnePlyBot<E> {
+    fn name(&self) -> String {
+        format!("One-ply {}", self.evaluator.name().to_lowercase())
+    }
+
+    fn choose_move(&mut self, board: &Board) -> Option<MoveOp> {
+        let moves = best_moves(board, |m| {
+            let mut after = board.clone();
+            if after.try_move(*m).is_err() {
+                return i32::MIN;
+            }
+            match after.termination() {
+                Some(termination) if termination.outcome == Outcome::Winner(board.to_play) => i32::MAX,
+                Some(_) => 0, // stalemate, dead position
+                None if after.claimable_draw().is_some() => 0,
+                None => -self.evaluator.evaluate(&after), // scored for the opponent, who is now to move
+            }
+        });
+        self.rng.pick(&moves)
+    }
+}
+
+// results of a batch of playouts, counted from white's side
+#[derive(Debug,Copy,Clone,Default,Eq,PartialEq)]
+pub struct Estimate {
+    pub white: u32,
+    pub draws: u32,
+    pub black: u32,
+}
+
+impl Estimate {
+    pub fn playouts(&self) -> u32 {
+        self.white + self.draws + self.black
+    }
+
+    // expected score for c, draws count half
+    pub fn score(&self, c: Color) -> f32 {
+        let wins = match c {
+            Color::White => self.white,
+            Color::Black => self.black,
+        };
+        (wins as f32 + self.draws as f32 / 2.0) / self.playouts().max(1) as f32
+    }
+}
+
+// a rough second opinion: greedy-capture games from start, up to max_plies each.
+// games still going at the limit are adjudicated on material, a minor piece up counts as a win
+pub fn estimate(start: &Board, playouts: u32, max_plies: usize, rng: &mut Rng) -> Estimate {
+    let mut estimate = Estimate::default();
+
+    for _ in 0..playouts {
+        let mut bot = GreedyCapture::new(Rng::new(rng.next_u64()));
+        let mut board = start.clone();
+
+        for _ in 0..max_plies {
+            match bot.choose_move(&board) {
+                Some(m) if board.try_move(m).is_ok() && board.termination().is_none() => (),
+                _ => break,
+            }
+        }
+
+        let outcome = match board.termination() {
+            Some(termination) => termination.outcome,
+            None => match material(&board, Color::White) {
+                m if m >= piece_value(PieceType::Knight) => Outcome::Winner(Color::White),
+                m if m <= -piece_value(PieceType::Knight) => Outcome::Winner(Color::Black),
+                _ => Outcome::Draw,
+            },
+        };
+        match outcome {
+            Outcome::Winner(Color::White) => estimate.white += 1,
+            Outcome::Winner(Color::Black) => estimate.black += 1,
+            Outcome::Draw => estimate.draws += 1,
+        }
+    }
+
+    estimate
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::board::*;
+    use crate::bots::*;
+    #[test]
+    fn bots_test() {
+        // black queen hangs on d4, the knight on h4 is worth less
+        let mut board = Board::from_fen("4k3/8/8/8/3q3n/8/3Q4/4K2R w - - 0 1").unwrap();
+
+        let mut random = RandomMover::new(Rng::new(7));
+        for _ in 0..20 {
+            let m = random.choose_move(&board).unwrap();
+            assert_eq!(board.is_legal(&m), Ok(()));
+        }
+
+        let mut greedy = GreedyCapture::new(Rng::new(7));
+        assert_eq!(greedy.choose_move(&board).map(|m| (m.from, m.to)), Some((51, 35)));
+
+        let mut material_bot = OnePlyBot::new(crate::eval::Material, Rng::new(7));
+        assert_eq!(material_bot.choose_move(&board).map(|m| (m.from, m.to)), Some((51, 35)));
+
+        // the material bot takes mate over material
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/q7/1R4K1 w - - 0 1").unwrap();
+        assert_eq!(material_bot.choose_move(&board).map(|m| (m.from, m.to)), Some((57, 1)));
+
+        // taking the bishop stalemates, a draw isn't worth more for being a knight up
+        let board = Board::from_fen("k7/p1K5/P7/8/8/8/4N3/6b1 w - - 0 1").unwrap();
+        let m = material_bot.choose_move(&board).unwrap();
+        assert_ne!((m.from, m.to), (52, 62));
+
+        // a queen down, the 50 move draw beats winning back a pawn
+        let board = Board::from_fen("4k3/q7/8/8/8/8/7p/7K w - - 99 80").unwrap();
+        assert_eq!(material_bot.choose_move(&board).map(|m| (m.from, m.to)), Some((63, 54)));
+
+        let board = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(random.choose_move(&board), None);
+    }
+
+    #[test]
+    fn estimate_test() {
+        // white is a queen up with the move
+        let board = Board::from_fen("4k3/8/8/8/8/8/3Q4/4K3 w - - 0 1").unwrap();
+        let e = estimate(&board, 20, 40, &mut Rng::new(3));
+        assert_eq!(e.playouts(), 20);
+        assert_eq!(e.black, 0);
+        assert!(e.white > 0 && e.score(Color::White) > e.score(Color::Black));
+
+        // already mated, every playout ends at once
+        let board = Board::from_fen("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(estimate(&board, 5, 40, &mut Rng::new(3)), Estimate { white: 5, draws: 0, black: 0 });
+    }
+}
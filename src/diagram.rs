@@ -0,0 +1,99 @@
+// Text diagrams of a position: framed ASCII (or Unicode) for forums and plain text,
+// and a LaTeX snippet for the chessboard package.
+use crate::board::{Board, Color, PieceType, PIECE_TYPES};
+use crate::notation::PieceStyle;
+
+const WHITE_FIGURINES: [char; PIECE_TYPES] = [' ', '♙', '♖', '♘', '♗', '♕', '♔', 'A', 'C'];
+const BLACK_FIGURINES: [char; PIECE_TYPES] = [' ', '♟', '♜', '♞', '♝', '♛', '♚', 'a', 'c'];
+
+fn piece_char(board: &Board, index: usize, style: PieceStyle) -> char {
+    let square = board.squares[index];
+    match (style, square.piece, square.color) {
+        (PieceStyle::Letter, PieceType::Empty, _) => '.',
+        (PieceStyle::Letter, piece, Color::White) => board.letter(piece),
+        (PieceStyle::Letter, piece, Color::Black) => board.letter(piece).to_ascii_lowercase(),
+        // fairies have no glyph, they keep their letters like the archbishop and chancellor
+        (PieceStyle::Figurine, piece, Color::White) => WHITE_FIGURINES.get(piece.index()).copied().unwrap_or(board.letter(piece)),
+        (PieceStyle::Figurine, piece, Color::Black) => BLACK_FIGURINES.get(piece.index()).copied().unwrap_or(board.letter(piece).to_ascii_lowercase()),
+    }
+}
+
+// white at the bottom, rank numbers on the left, files underneath
+pub fn text(board: &Board, style: PieceStyle) -> String {
+    let (height, width) = board.shape;
+    let (top, side, bottom) = match style {
+        PieceStyle::Letter   => (('+', '-', '+'), '|', ('+', '-', '+')),
+        PieceStyle::Figurine => (('┌', '─', '┐'), '│', ('└', '─', '┘')),
+    };
+    let margin = height.to_string().len();
+    let rule = |(left, fill, right): (char, char, char)| {
+        format!("{} {}{}{}\n", " ".repeat(margin), left, fill.to_string().repeat(width * 2 + 1), right)
+    };
+
+    let mut diagram = rule(top);
+    for row in 0..height {
+        diagram.push_str(&format!("{:>margin$} {}", height - row, side, margin = margin));
+        for file in 0..width {
+            diagram.push(' ');
+            diagram.push(piece_char(board, row * width + file, style));
+        }
+        diagram.push_str(&format!(" {}\n", side));
+    }
+    diagram.push_str(&rule(bottom));
+
+    diagram.push_str(&" ".repeat(margin + 2));
+    for file in 0..width {
+        diagram.push(' ');
+        diagram.push((b'a' + file as u8) as char);
+    }
+    diagram.push('\n');
+
+    diagram
+}
+
+// \chessboard from the chessboard package (also loaded by xskak); uppercase pieces are white
+pub fn latex(board: &Board) -> String {
+    let pieces: Vec<String> = (0..board.squares.len())
+        .filter(|&index| board.squares[index].piece != PieceType::Empty)
+        .map(|index| format!("{}{}", piece_char(board, index, PieceStyle::Letter), board.index_to_alg(index)))
+        .collect();
+
+    let mut options = vec![
+        format!("maxfield={}", board.index_to_alg(board.shape.1 - 1)),
+        format!("setpieces={{{}}}", pieces.join(",")),
+        "showmover=true".to_string(),
+    ];
+    if board.to_play == Color::Black {
+        options.push("mover=b".to_string());
+    }
+
+    format!("\\chessboard[{}]\n", options.join(",\n  "))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::board::*;
+    use crate::diagram::*;
+    #[test]
+    fn diagram_test() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1").unwrap();
+
+        assert_eq!(text(&board, PieceStyle::Letter), concat!(
+            "  +-----------------+\n",
+            "8 | . . . . k . . . |\n",
+            "7 | . . . . . . . . |\n",
+            "6 | . . . . . . . . |\n",
+            "5 | . . . . . . . . |\n",
+            "4 | . . . . . . . . |\n",
+            "3 | . . . . . . . . |\n",
+            "2 | . . . . P . . . |\n",
+            "1 | . . . . K . . . |\n",
+            "  +-----------------+\n",
+            "    a b c d e f g h\n",
+        ));
+        assert!(text(&board, PieceStyle::Figurine).contains("2 │         ♙       │\n"));
+
+        assert_eq!(latex(&board), "\\chessboard[maxfield=h8,\n  setpieces={ke8,Pe2,Ke1},\n  showmover=true,\n  mover=b]\n");
+    }
+}
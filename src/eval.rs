@@ -0,0 +1,67 @@
+// Static evaluation. Scores are centipawns from the point of view of the side to move,
+// so anything searching over them can negate instead of branching on color.
+use crate::board::{Board, Color, PieceType};
+
+pub trait Evaluator {
+    fn name(&self) -> &'static str;
+
+    fn evaluate(&self, board: &Board) -> i32;
+}
+
+pub fn piece_value(piece: PieceType) -> i32 {
+    match piece {
+        PieceType::Empty  => 0,
+        PieceType::Pawn   => 100,
+        PieceType::Knight => 300,
+        PieceType::Bishop => 300,
+        PieceType::Rook   => 500,
+        PieceType::Queen  => 900,
+        PieceType::King   => 0,
+        PieceType::Archbishop => 875,
+        PieceType::Chancellor => 900,
+        PieceType::Fairy(_) => 0, // defined per variant, see value_on
+    }
+}
+
+// piece_value, or the value the board's variant gives a fairy piece
+pub fn value_on(board: &Board, piece: PieceType) -> i32 {
+    board.fairy(piece).map_or(piece_value(piece), |def| def.value)
+}
+
+// material balance from c's point of view
+pub fn material(board: &Board, c: Color) -> i32 {
+    board.squares.iter()
+        .map(|s| if s.color == c { value_on(board, s.piece) } else { -value_on(board, s.piece) })
+        .sum()
+}
+
+// nothing but piece counting
+pub struct Material;
+
+impl Evaluator for Material {
+    fn name(&self) -> &'static str {
+        "Material"
+    }
+
+    fn evaluate(&self, board: &Board) -> i32 {
+        material(board, board.to_play)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::board::*;
+    use crate::eval::*;
+    #[test]
+    fn material_test() {
+        assert_eq!(Material.evaluate(&Board::from_fen(START_FEN).unwrap()), 0);
+
+        let board = Board::odds_start(Color::Black, Odds::Queen);
+        assert_eq!(Material.evaluate(&board), 900);
+        assert_eq!(material(&board, Color::Black), -900);
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/3p4/R3K3 b Q - 0 1").unwrap();
+        assert_eq!(Material.evaluate(&board), -400);
+    }
+}
@@ -0,0 +1,186 @@
+// Fairy pieces defined at runtime. A definition gives the piece's movement as leaps and
+// rides (see board::Movement), its FEN letter, a value for the bots and optionally its
+// artwork. Definitions go into a Registry, and a board carries the registry it was set up
+// with (Board::from_fen_with), so boards of different variants can live side by side.
+use crate::board::{Movement, PieceType};
+
+#[derive(Debug,Clone,Eq,PartialEq,Default)]
+pub struct FairyDef {
+    pub name: String,
+    pub letter: char, // white's FEN letter, black's is the lowercase
+    pub leaps: Vec<(i16, i16)>, // (rank, file)
+    pub rides: Vec<(i16, i16)>,
+    pub value: i32, // centipawns
+    pub svg: Option<(Vec<u8>, Vec<u8>)>, // white and black artwork, without it the gui draws the letter
+}
+
+impl FairyDef {
+    pub fn movement(&self) -> Movement<'_> {
+        Movement { leaps: &self.leaps, rides: &self.rides }
+    }
+}
+
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+pub enum FairyError {
+    BadLetter(char),        // FEN letters are uppercase A-Z
+    LetterTaken(char),      // a built in piece or another fairy has it
+    NoMoves,
+    BadOffset((i16, i16)),  // zero, or missing its opposite. attackers_of relies on the symmetry
+}
+
+// the fairy pieces of one variant. a piece is PieceType::Fairy(slot) for as long as it is
+// defined, the letters (A-Z less the built in ones) keep the number of slots small
+#[derive(Debug,Clone,Eq,PartialEq,Default)]
+pub struct Registry {
+    slots: Vec<Option<FairyDef>>, // a removed piece leaves its slot empty for the next definition
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // add a piece, handing back the PieceType boards and moves use for it. the same
+    // definition again gives the piece already made from it
+    pub fn define(&mut self, def: FairyDef) -> Result<PieceType, FairyError> {
+        if !def.letter.is_ascii_uppercase() {
+            return Err(FairyError::BadLetter(def.letter));
+        }
+        if def.leaps.is_empty() && def.rides.is_empty() {
+            return Err(FairyError::NoMoves);
+        }
+        for offsets in [&def.leaps, &def.rides] {
+            if let Some(&bad) = offsets.iter().find(|&&(dr, df)| (dr, df) == (0, 0) || !offsets.contains(&(-dr, -df))) {
+                return Err(FairyError::BadOffset(bad));
+            }
+        }
+
+        if let Some(piece) = self.pieces().find(|&p| self.get(p) == Some(&def)) {
+            return Ok(piece);
+        }
+        if PieceType::from_letter(def.letter).is_some() || self.from_letter(def.letter).is_some() {
+            return Err(FairyError::LetterTaken(def.letter));
+        }
+
+        let slot = match self.slots.iter().position(Option::is_none) {
+            Some(slot) => slot,
+            None => {
+                self.slots.push(None);
+                self.slots.len() - 1
+            },
+        };
+        self.slots[slot] = Some(def);
+
+        Ok(PieceType::Fairy(slot as u8))
+    }
+
+    // take a piece out again, for redefining it. boards set up with a registry hold on to
+    // their own copy, so this only affects boards made afterwards
+    pub fn remove(&mut self, piece: PieceType) -> Option<FairyDef> {
+        match piece {
+            PieceType::Fairy(slot) => self.slots.get_mut(slot as usize)?.take(),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, piece: PieceType) -> Option<&FairyDef> {
+        match piece {
+            PieceType::Fairy(slot) => self.slots.get(slot as usize)?.as_ref(),
+            _ => None,
+        }
+    }
+
+    // every piece defined, by slot
+    pub fn pieces(&self) -> impl Iterator<Item = PieceType> + '_ {
+        self.slots.iter().enumerate()
+            .filter(|(_, def)| def.is_some())
+            .map(|(slot, _)| PieceType::Fairy(slot as u8))
+    }
+
+    // white's FEN letter, 'S' -> the piece defined with it
+    pub fn from_letter(&self, c: char) -> Option<PieceType> {
+        self.pieces().find(|&p| self.get(p).is_some_and(|def| def.letter == c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+    use crate::board::*;
+    use crate::fairy::*;
+    #[test]
+    fn define_test() {
+        let nightrider = FairyDef {
+            name: "Nightrider".to_string(),
+            letter: 'S',
+            rides: vec![(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)],
+            value: 600,
+            ..Default::default()
+        };
+        let mut registry = Registry::new();
+        let piece = registry.define(nightrider.clone()).unwrap();
+        assert_eq!(registry.define(nightrider.clone()), Ok(piece));
+        assert_eq!(registry.from_letter('S'), Some(piece));
+
+        assert_eq!(registry.define(FairyDef { letter: 'N', ..nightrider.clone() }), Err(FairyError::LetterTaken('N')));
+        assert_eq!(registry.define(FairyDef { letter: 'S', value: 1, ..nightrider.clone() }), Err(FairyError::LetterTaken('S')));
+        assert_eq!(registry.define(FairyDef { letter: 's', ..nightrider.clone() }), Err(FairyError::BadLetter('s')));
+        assert_eq!(registry.define(FairyDef { letter: 'T', rides: vec![(1, 2)], ..nightrider.clone() }), Err(FairyError::BadOffset((1, 2))));
+        assert_eq!(registry.define(FairyDef { letter: 'T', rides: vec![], ..nightrider.clone() }), Err(FairyError::NoMoves));
+
+        // on the board: FEN letters, moves along the ride until blocked, checks
+        let registry = Arc::new(registry);
+        let mut board = Board::from_fen_with("4k3/8/8/8/8/8/8/S3K3 w - - 0 1", registry.clone()).unwrap();
+        assert_eq!(board.squares[56].piece, piece);
+        assert_eq!(board.letter(piece), 'S');
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/S3K3 w - - 0 1");
+        let mut targets = board.legal_moves_from(56).iter().map(|m| m.to).collect::<Vec<_>>();
+        targets.sort();
+        assert_eq!(targets.iter().map(|&i| board.index_to_alg(i)).collect::<Vec<_>>(), ["d7", "c5", "g4", "b3", "e3", "c2"].map(String::from));
+        let m = board.parse_san("Sd7").unwrap();
+        assert_eq!((board.san(&m), board.index_to_alg(m.to)), ("Sd7".to_string(), "d7".to_string()));
+        assert_eq!(crate::eval::value_on(&board, piece), 600);
+        assert_ne!(board.key(), Board::from_fen("4k3/8/8/8/8/8/8/N3K3 w - - 0 1").unwrap().key());
+
+        let board = Board::from_fen_with("4k3/8/8/2s5/8/8/8/4K3 w - - 0 1", registry.clone()).unwrap();
+        assert!(board.is_in_check(Color::White)); // c5, d3, e1
+        let board = Board::from_fen_with("4k3/8/8/2s5/8/3P4/8/4K3 w - - 0 1", registry.clone()).unwrap();
+        assert!(!board.is_in_check(Color::White));
+
+        // boards without the registry don't know the letter
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/S3K3 w - - 0 1").err(), Some(1));
+    }
+
+    #[test]
+    fn variants_test() {
+        // the same letter for a different piece in another variant
+        let mut nightriders = Registry::new();
+        let nightrider = nightriders.define(FairyDef {
+            letter: 'S',
+            rides: vec![(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)],
+            ..Default::default()
+        }).unwrap();
+        let mut wazirs = Registry::new();
+        wazirs.define(FairyDef { letter: 'S', leaps: vec![(1, 0), (-1, 0), (0, 1), (0, -1)], ..Default::default() }).unwrap();
+
+        let fen = "4k3/8/8/8/8/8/8/S3K3 w - - 0 1";
+        let mut rider = Board::from_fen_with(fen, Arc::new(nightriders.clone())).unwrap();
+        let mut wazir = Board::from_fen_with(fen, Arc::new(wazirs)).unwrap();
+        assert_eq!(rider.legal_moves_from(56).len(), 6);
+        assert_eq!(wazir.legal_moves_from(56).len(), 2);
+        assert!(rider != wazir);
+
+        // removing frees the letter and the slot, boards already set up keep the old piece
+        assert_eq!(nightriders.remove(nightrider).map(|def| def.letter), Some('S'));
+        assert_eq!(nightriders.remove(nightrider), None);
+        assert_eq!(nightriders.from_letter('S'), None);
+        let camel = nightriders.define(FairyDef {
+            letter: 'L',
+            leaps: vec![(-3, -1), (-3, 1), (-1, -3), (-1, 3), (1, -3), (1, 3), (3, -1), (3, 1)],
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(camel, nightrider);
+        assert_eq!(rider.legal_moves_from(56).len(), 6);
+    }
+}
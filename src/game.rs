@@ -0,0 +1,313 @@
+// A game: the starting position and the moves played from it, with a cursor that can
+// step back and forth through them. Playing a move away from the end starts a new line
+// and drops the moves that came after.
+use std::ops::Deref;
+use crate::board::{Board, GameResult, MoveError, MoveList, MoveOp, Undo, START_FEN};
+use crate::notation::PieceStyle;
+use crate::bots::Rng;
+use crate::pgn::{Node, PgnGame, Tags};
+
+#[derive(Debug,Clone,Eq,PartialEq)]
+pub struct Ply {
+    pub moveop: MoveOp,
+    pub san: String, // with check marks, in the position before the move
+    pub fan: String, // the same with figurines
+    pub fen: String, // position after the move
+}
+
+#[derive(Clone)]
+pub struct Game {
+    pub tags: Tags,
+    start: Board,
+    plies: Vec<Ply>,
+    current: usize, // plies played to reach board
+    board: Board,
+    undos: Vec<Undo>, // one per ply played to reach board, to step back without replaying from the start
+    end: GameResult, // how the last ply left things, can say more than the position (a resignation)
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new(Board::from_fen(START_FEN).unwrap())
+    }
+}
+
+// the current position, so a game can be used wherever a board is read
+impl Deref for Game {
+    type Target = Board;
+
+    fn deref(&self) -> &Board {
+        &self.board
+    }
+}
+
+impl Game {
+    pub fn new(start: Board) -> Self {
+        let mut tags = Tags::default();
+        if start.to_fen() != START_FEN {
+            tags.set("SetUp", "1");
+            tags.set("FEN", &start.to_fen());
+        }
+
+        Game { tags, end: start.result, start: start.clone(), plies: Vec::new(), current: 0, board: start, undos: Vec::new() }
+    }
+
+    // the main line of a PGN game, positioned after its last move
+    pub fn from_pgn(pgn: &PgnGame) -> Self {
+        let mut game = Game::new(pgn.start.clone());
+        game.tags = pgn.tags.clone();
+        for m in pgn.moves() {
+            game.push(m);
+        }
+        game.board.result = pgn.board().result; // can be one only the tags know, e.g. a resignation
+        game.end = game.board.result;
+
+        game
+    }
+
+    // main line only, the tree has no variations of its own
+    pub fn to_pgn(&self) -> PgnGame {
+        PgnGame {
+            tags: self.tags.clone(),
+            start: self.start.clone(),
+            mainline: self.plies.iter().map(|ply| Node::new(ply.moveop)).collect(),
+        }
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn start(&self) -> &Board {
+        &self.start
+    }
+
+    pub fn plies(&self) -> &[Ply] {
+        &self.plies
+    }
+
+    // legal moves of the piece on index in the current position
+    pub fn legal_moves_from(&mut self, index: usize) -> MoveList {
+        self.board.legal_moves_from(index)
+    }
+
+    // how many plies into the game the current position is
+    pub fn ply(&self) -> usize {
+        self.current
+    }
+
+    // play moveop in the current position, replacing whatever followed it
+    pub fn play(&mut self, moveop: MoveOp) -> Result<(), MoveError> {
+        self.board.is_legal(&moveop)?;
+
+        self.plies.truncate(self.current);
+        self.push(moveop);
+        self.end = self.board.result;
+        self.tags.set("Result", self.board.result.pgn_result());
+
+        Ok(())
+    }
+
+    // take the draw by repetition or the 50 move rule the current position offers, dropping
+    // whatever followed it. false if there is none to claim
+    pub fn claim_draw(&mut self) -> bool {
+        if !self.board.claim_draw() {
+            return false;
+        }
+
+        self.plies.truncate(self.current);
+        self.end = self.board.result;
+        self.tags.set("Result", self.board.result.pgn_result());
+
+        true
+    }
+
+    // the game so far plus random legal moves from the current position, up to max_plies more
+    // or until the game ends. the same seed always gives the same game, handy for fuzzing
+    pub fn random_playout(&self, seed: u64, max_plies: usize) -> Game {
+        let mut rng = Rng::new(seed);
+        let mut game = self.clone();
+
+        for _ in 0..max_plies {
+            if game.termination().is_some() {
+                break;
+            }
+            match rng.pick(&game.board.get_legal_moves()) {
+                Some(m) => game.play(m).expect("picked from the legal moves"),
+                None => break,
+            }
+        }
+
+        game
+    }
+
+    fn push(&mut self, moveop: MoveOp) {
+        let san = self.board.san(&moveop);
+        let fan = self.board.san_styled(&moveop, PieceStyle::Figurine);
+        self.undos.push(self.board.make_move(moveop));
+        self.board.update_result();
+
+        self.plies.push(Ply { moveop, san, fan, fen: self.board.to_fen() });
+        self.current += 1;
+    }
+
+    // the Result tag follows undo, redo and goto, a mate taken back is an unfinished game again
+    pub fn undo(&mut self) -> bool {
+        match self.undos.pop() {
+            Some(undo) => {
+                self.board.unmake_move(undo);
+                self.current -= 1;
+                self.tags.set("Result", self.board.result.pgn_result());
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let moveop = match self.plies.get(self.current) {
+            Some(ply) => ply.moveop,
+            None => return false,
+        };
+
+        self.undos.push(self.board.make_move(moveop)); // legal when it was played
+        self.board.update_result();
+        self.current += 1;
+        if self.current == self.plies.len() {
+            self.board.result = self.end;
+        }
+        self.tags.set("Result", self.board.result.pgn_result());
+
+        true
+    }
+
+    // jump to the position after the first ply moves, false if the game isn't that long.
+    // steps there from the current position, one make or unmake per ply in between
+    pub fn goto(&mut self, ply: usize) -> bool {
+        if ply > self.plies.len() {
+            return false;
+        }
+
+        while self.current > ply {
+            self.undo();
+        }
+        while self.current < ply {
+            self.redo();
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::board::*;
+    use crate::game::*;
+    #[test]
+    fn undo_redo_test() {
+        let mut game = Game::default();
+        for text in ["e4", "e5", "Nf3", "Nc6"] {
+            let m = game.parse_san(text).unwrap();
+            game.play(m).unwrap();
+        }
+        assert_eq!(game.plies().iter().map(|p| p.san.as_str()).collect::<Vec<_>>(), vec!["e4", "e5", "Nf3", "Nc6"]);
+        assert_eq!(game.plies()[3].fen, game.to_fen());
+
+        assert!(game.undo() && game.undo());
+        assert_eq!(game.ply(), 2);
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
+        assert!(game.redo());
+        assert_eq!(game.to_fen(), game.plies()[2].fen);
+
+        // a new move replaces the rest of the line
+        let m = game.parse_san("d6").unwrap();
+        game.play(m).unwrap();
+        assert_eq!(game.plies().len(), 4);
+        assert!(!game.redo());
+        assert_eq!(game.play(MoveOp{from: 57, to: 41, ..Default::default()}), Err(MoveError::IllegalPieceMove));
+
+        assert!(game.goto(0) && !game.undo());
+        assert!(*game.board() == *game.start());
+        assert!(!game.goto(5));
+
+        // stepping around gives the same positions, clocks and history as playing the moves
+        assert!(game.goto(3) && game.goto(1) && game.goto(4));
+        let mut replayed = game.start().clone();
+        for ply in game.plies() {
+            replayed.try_move(ply.moveop).unwrap();
+        }
+        assert!(*game.board() == replayed && game.board().history == replayed.history);
+        assert_eq!(game.to_fen(), replayed.to_fen());
+
+        // through PGN and back
+        let pgn = game.to_pgn();
+        let copy = Game::from_pgn(&pgn);
+        assert_eq!(copy.plies(), game.plies());
+        assert_eq!(copy.ply(), 4);
+    }
+
+    #[test]
+    fn undo_mate_test() {
+        let mut game = Game::default();
+        for text in ["f3", "e5", "g4", "Qh4#"] {
+            let m = game.parse_san(text).unwrap();
+            game.play(m).unwrap();
+        }
+        assert_eq!(game.result, GameResult::BlackCheckmate);
+        assert_eq!(game.tags.get("Result"), Some("0-1"));
+
+        assert!(game.undo());
+        assert_eq!(game.result, GameResult::Active);
+        assert_eq!(game.tags.get("Result"), Some("*"));
+        assert!(game.to_pgn().to_string().trim_end().ends_with('*'));
+
+        assert!(game.redo());
+        assert_eq!(game.tags.get("Result"), Some("0-1"));
+
+        // a resignation only the tags know about comes back with the last move too
+        let pgn: PgnGame = "[Result \"1-0\"]\n\n1. e4 e5 1-0".parse().unwrap();
+        let mut game = Game::from_pgn(&pgn);
+        assert!(game.undo() && game.tags.get("Result") == Some("*"));
+        assert!(game.redo() && game.result == GameResult::WhiteResign);
+        assert_eq!(game.tags.get("Result"), Some("1-0"));
+    }
+
+    #[test]
+    fn claim_draw_test() {
+        let mut game = Game::default();
+        assert!(!game.claim_draw());
+        for text in ["Nf3", "Nf6", "Ng1", "Ng8"].iter().cycle().take(8) {
+            let m = game.parse_san(text).unwrap();
+            game.play(m).unwrap();
+        }
+        assert_eq!(game.result, GameResult::Active);
+        assert_eq!(game.claimable_draw(), Some(GameResult::DrawThreefold));
+
+        assert!(game.claim_draw());
+        assert_eq!(game.tags.get("Result"), Some("1/2-1/2"));
+        assert!(game.undo() && game.result == GameResult::Active);
+        assert!(game.redo() && game.result == GameResult::DrawThreefold);
+    }
+
+    #[test]
+    fn random_playout_test() {
+        let mut game = Game::default();
+        let m = game.parse_san("e4").unwrap();
+        game.play(m).unwrap();
+
+        let playout = game.random_playout(5, 20);
+        assert_eq!(playout.plies().len(), 21);
+        assert_eq!(playout.plies()[0].san, "e4");
+        assert_eq!(playout.plies(), game.random_playout(5, 20).plies());
+        assert_ne!(playout.plies(), game.random_playout(6, 20).plies());
+
+        // stops at the end of the game, and can be stepped through like any other
+        let mut mated = Game::new(Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap()).random_playout(1, 20);
+        assert!(mated.plies().is_empty());
+        let mut playout = game.random_playout(5, 300);
+        assert!(playout.termination().is_some() || playout.plies().len() == 301);
+        assert!(playout.goto(10) && playout.to_fen() == playout.plies()[9].fen);
+        assert!(!mated.undo());
+    }
+}
@@ -1,36 +1,179 @@
 use eframe::egui;
-use eframe::egui::include_image;
 use eframe::egui::pos2;
 
 use epaint::{
     Color32,
     Rect,
+    Shape,
 };
 
 use std::collections::HashMap;
 
 use crate::board;
+use crate::bots;
+use crate::diagram;
+use crate::eval;
+use crate::game;
+use crate::notation;
+use crate::pgn;
+
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+enum Action {
+    NewGame,
+    Deselect,
+    Fullscreen,
+    Undo,
+    Redo,
+    PrevMove,
+    NextMove,
+    Flip,
+}
+
+impl Action {
+    fn label(&self) -> &'static str {
+        match self {
+            Action::NewGame    => "New game",
+            Action::Deselect   => "Deselect",
+            Action::Fullscreen => "Toggle fullscreen",
+            Action::Undo       => "Take back",
+            Action::Redo       => "Replay move",
+            Action::PrevMove   => "Previous move",
+            Action::NextMove   => "Next move",
+            Action::Flip       => "Flip board",
+        }
+    }
+
+    // name the binding is saved under, kept stable when labels change
+    fn key(&self) -> &'static str {
+        match self {
+            Action::NewGame    => "new_game",
+            Action::Deselect   => "deselect",
+            Action::Fullscreen => "fullscreen",
+            Action::Undo       => "undo",
+            Action::Redo       => "redo",
+            Action::PrevMove   => "prev_move",
+            Action::NextMove   => "next_move",
+            Action::Flip       => "flip",
+        }
+    }
+}
+
+fn default_shortcuts() -> Vec<(Action, egui::KeyboardShortcut)> {
+    vec![
+        (Action::NewGame,    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::N)),
+        (Action::Deselect,   egui::KeyboardShortcut::new(egui::Modifiers::NONE, egui::Key::Escape)),
+        (Action::Fullscreen, egui::KeyboardShortcut::new(egui::Modifiers::NONE, egui::Key::F11)),
+        (Action::Undo,       egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Z)),
+        (Action::Redo,       egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Y)),
+        (Action::PrevMove,   egui::KeyboardShortcut::new(egui::Modifiers::NONE, egui::Key::ArrowLeft)),
+        (Action::NextMove,   egui::KeyboardShortcut::new(egui::Modifiers::NONE, egui::Key::ArrowRight)),
+        (Action::Flip,       egui::KeyboardShortcut::new(egui::Modifiers::NONE, egui::Key::F)),
+    ]
+}
+
+// the action other than action that shortcut is already bound to
+fn clash(shortcuts: &[(Action, egui::KeyboardShortcut)], action: Action, shortcut: egui::KeyboardShortcut) -> Option<Action> {
+    shortcuts.iter().find(|(a, s)| *a != action && *s == shortcut).map(|(a, _)| *a)
+}
+
+// bind shortcut to action, unless another action has it already
+fn rebind(shortcuts: &mut [(Action, egui::KeyboardShortcut)], action: Action, shortcut: egui::KeyboardShortcut) -> Result<(), Action> {
+    if let Some(other) = clash(shortcuts, action, shortcut) {
+        return Err(other);
+    }
+    if let Some(binding) = shortcuts.iter_mut().find(|(a, _)| *a == action) {
+        binding.1 = shortcut;
+    }
+
+    Ok(())
+}
+
+// saved bindings over the defaults. anything unknown is dropped, and if the result has two
+// actions on one key (say a new action's default was taken) it's the defaults again
+fn restore_shortcuts(saved: Vec<(String, egui::KeyboardShortcut)>) -> Vec<(Action, egui::KeyboardShortcut)> {
+    let mut shortcuts = default_shortcuts();
+    for (action, shortcut) in shortcuts.iter_mut() {
+        if let Some((_, saved)) = saved.iter().find(|(key, _)| key == action.key()) {
+            *shortcut = *saved;
+        }
+    }
+
+    match shortcuts.iter().any(|&(action, shortcut)| clash(&shortcuts, action, shortcut).is_some()) {
+        true => default_shortcuts(),
+        false => shortcuts,
+    }
+}
+
+// what pasted text holds, if it's anything we can load: a FEN of a position that could come up
+// in a game, or else the first game of a PGN with at least one move
+fn detect_paste(text: &str) -> Option<(&'static str, game::Game)> {
+    match board::Board::from_fen_lenient(text.trim()).ok().filter(|b| b.validate().is_ok()) {
+        Some(position) => Some(("a FEN position", game::Game::new(position))),
+        None => pgn::read_games(text).into_iter().next()
+            .and_then(Result::ok)
+            .filter(|game| !game.mainline.is_empty())
+            .map(|game| ("a PGN game", game::Game::from_pgn(&game))),
+    }
+}
 
 pub struct ChessGUI {
-    game: board::Board,
-    piece_assets: HashMap<(board::Color, board::PieceType), egui::Image>
+    game: game::Game,
+    piece_assets: HashMap<(board::Color, board::PieceType), egui::Image<'static>>,
+    board_shapes: Vec<Shape>,
+    drawn: Option<(Vec<board::Square>, Rect, Option<usize>, f32)>, // position, board area, selection and turn board_shapes was built for
+    selected: Option<usize>,
+    selected_moves: board::MoveList,
+    pasted: Option<(&'static str, game::Game)>, // what was found on the clipboard, waiting for the user to load it
+    shortcuts: Vec<(Action, egui::KeyboardShortcut)>,
+    rebinding: Option<Action>, // waiting for the next key press to bind to this action
+    clash: Option<Action>, // the last key pressed while rebinding already belongs to this action
+    opponent: Option<Box<dyn bots::Bot>>, // plays black, None for two humans at one board
+    estimate: Option<(board::Board, bots::Estimate)>, // playout estimate and the position it was made for
+    estimating: Option<(board::Board, std::thread::JoinHandle<bots::Estimate>)>, // playouts still running on a worker thread
+    flip: bool, // black at the bottom, by hand
+    auto_flip: bool, // hotseat only: turn the board to face whoever is to move
+    privacy: bool, // hotseat only: hide the board after each move until the next player is ready
+    screened: bool, // the board is hidden right now
+    notation: notation::PieceStyle, // letters or figurines in the move list
 }
 
 impl Default for ChessGUI {
     fn default() -> Self {
         Self {
-            game: board::Board::from_fen(board::START_FEN).unwrap(),
+            game: game::Game::default(),
             piece_assets: Self::gen_piece_assets(),
+            board_shapes: Vec::new(),
+            drawn: None,
+            selected: None,
+            selected_moves: board::MoveList::new(),
+            pasted: None,
+            shortcuts: default_shortcuts(),
+            rebinding: None,
+            clash: None,
+            opponent: None,
+            estimate: None,
+            estimating: None,
+            flip: false,
+            auto_flip: false,
+            privacy: false,
+            screened: false,
+            notation: notation::PieceStyle::Letter,
         }
     }
 }
 
 impl ChessGUI{
+    const SHORTCUTS_KEY: &'static str = "shortcuts";
     const DARK_SQ_COLOR: epaint::Color32 =  epaint::Color32::from_rgb(115,66,7);
     const LIGHT_SQ_COLOR: epaint::Color32 = epaint::Color32::from_rgb(237,178,107);
+    const SELECTED_SQ_COLOR: epaint::Color32 = epaint::Color32::from_rgba_premultiplied(60,90,20,90);
+    const MOVE_HINT_COLOR: epaint::Color32 = epaint::Color32::from_rgba_premultiplied(30,40,10,110);
     const DEF_SQ_SIZE: f32 = 75.;
+    const TEXTURE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+    const ESTIMATE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+    const FLIP_TIME: f32 = 0.4; // seconds
 
-    fn gen_piece_assets() -> HashMap<(board::Color, board::PieceType), egui::Image> {
+    fn gen_piece_assets() -> HashMap<(board::Color, board::PieceType), egui::Image<'static>> {
         HashMap::from([
             ((board::Color::White, board::PieceType::Pawn),     egui::Image::new(egui::include_image!("../resource/svg/pieces/white_pawn.svg"))),
             ((board::Color::White, board::PieceType::King),     egui::Image::new(egui::include_image!("../resource/svg/pieces/white_king.svg"))),
@@ -38,75 +181,646 @@ impl ChessGUI{
             ((board::Color::White, board::PieceType::Bishop),   egui::Image::new(egui::include_image!("../resource/svg/pieces/white_bishop.svg"))),
             ((board::Color::White, board::PieceType::Knight),   egui::Image::new(egui::include_image!("../resource/svg/pieces/white_knight.svg"))),
             ((board::Color::White, board::PieceType::Rook),     egui::Image::new(egui::include_image!("../resource/svg/pieces/white_rook.svg"))),
+            ((board::Color::White, board::PieceType::Archbishop), egui::Image::new(egui::include_image!("../resource/svg/pieces/white_archbishop.svg"))),
+            ((board::Color::White, board::PieceType::Chancellor), egui::Image::new(egui::include_image!("../resource/svg/pieces/white_chancellor.svg"))),
             ((board::Color::Black, board::PieceType::Pawn),     egui::Image::new(egui::include_image!("../resource/svg/pieces/black_pawn.svg"))),
             ((board::Color::Black, board::PieceType::King),     egui::Image::new(egui::include_image!("../resource/svg/pieces/black_king.svg"))),
             ((board::Color::Black, board::PieceType::Queen),    egui::Image::new(egui::include_image!("../resource/svg/pieces/black_queen.svg"))),
             ((board::Color::Black, board::PieceType::Bishop),   egui::Image::new(egui::include_image!("../resource/svg/pieces/black_bishop.svg"))),
             ((board::Color::Black, board::PieceType::Knight),   egui::Image::new(egui::include_image!("../resource/svg/pieces/black_knight.svg"))),
             ((board::Color::Black, board::PieceType::Rook),     egui::Image::new(egui::include_image!("../resource/svg/pieces/black_rook.svg"))),
+            ((board::Color::Black, board::PieceType::Archbishop), egui::Image::new(egui::include_image!("../resource/svg/pieces/black_archbishop.svg"))),
+            ((board::Color::Black, board::PieceType::Chancellor), egui::Image::new(egui::include_image!("../resource/svg/pieces/black_chancellor.svg"))),
         ])
     }
+
+    // artwork for the fairies of the loaded game's variant, the ones that came with it
+    fn load_fairy_assets(&mut self) {
+        self.piece_assets.retain(|&(_, piece), _| !matches!(piece, board::PieceType::Fairy(_)));
+        for piece in self.game.fairies.pieces() {
+            if let Some(def) = self.game.fairy(piece) {
+                if let Some((white, black)) = def.svg.clone() {
+                    for (color, svg) in [(board::Color::White, white), (board::Color::Black, black)] {
+                        let uri = format!("bytes://fairy/{}/{:?}.svg", def.name, color).to_lowercase();
+                        self.piece_assets.insert((color, piece), egui::Image::from_bytes(uri, svg));
+                    }
+                }
+            }
+        }
+    }
+
+    // the app with whatever settings were saved last time
+    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        let mut gui = Self::default();
+        if let Some(saved) = cc.storage.and_then(|storage| eframe::get_value(storage, Self::SHORTCUTS_KEY)) {
+            gui.shortcuts = restore_shortcuts(saved);
+        }
+
+        gui
+    }
+
+    fn perform(&mut self, ctx: &egui::Context, action: Action) {
+        match action {
+            Action::NewGame => self.load_position(board::Board::from_fen(board::START_FEN).unwrap()),
+            Action::Deselect => {
+                self.selected = None;
+                self.selected_moves.clear();
+            },
+            Action::Fullscreen => {
+                let fullscreen = ctx.input(|i| i.viewport().fullscreen.unwrap_or(false));
+                ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(!fullscreen));
+            },
+            Action::Undo => self.step(game::Game::undo),
+            Action::Redo => self.step(game::Game::redo),
+            Action::PrevMove => self.browse(game::Game::undo),
+            Action::NextMove => self.browse(game::Game::redo),
+            Action::Flip => self.flip = !self.flip,
+        }
+    }
+
+    // back or forth through the game, a bot waits until we're back at the end
+    fn browse(&mut self, step: impl FnOnce(&mut game::Game) -> bool) {
+        step(&mut self.game);
+        self.selected = None;
+        self.selected_moves.clear();
+    }
+
+    // undo or redo. against a bot that goes a full move, back to white, or it would just play again
+    fn step(&mut self, step: fn(&mut game::Game) -> bool) {
+        if step(&mut self.game) && self.opponent.is_some() && self.game.to_play == board::Color::Black {
+            step(&mut self.game);
+        }
+        self.selected = None;
+        self.selected_moves.clear();
+    }
+
+    // either bind the next key press to the action being rebound, or run whatever was pressed
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        if let Some(action) = self.rebinding {
+            let pressed = ctx.input(|i| i.events.iter().find_map(|event| match event {
+                egui::Event::Key{key, pressed: true, modifiers, ..} => Some(egui::KeyboardShortcut::new(*modifiers, *key)),
+                _ => None,
+            }));
+
+            // a key another action already has is refused, the rebind waits for a different one
+            if let Some(shortcut) = pressed {
+                self.clash = rebind(&mut self.shortcuts, action, shortcut).err();
+                if self.clash.is_none() {
+                    self.rebinding = None;
+                }
+            }
+            return;
+        }
+
+        for (action, shortcut) in self.shortcuts.clone() {
+            if ctx.input_mut(|i| i.consume_shortcut(&shortcut)) {
+                self.perform(ctx, action);
+            }
+        }
+    }
+
+    fn shortcuts_menu(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("shortcuts").show(ui, |ui| {
+            for (action, shortcut) in self.shortcuts.clone() {
+                ui.label(action.label());
+                let text = match self.rebinding {
+                    Some(rebinding) if rebinding == action => "press a key...".to_string(),
+                    _ => ui.ctx().format_shortcut(&shortcut),
+                };
+                if ui.button(text).clicked() {
+                    self.rebinding = Some(action);
+                    self.clash = None;
+                }
+                ui.end_row();
+            }
+        });
+
+        if let Some(clash) = self.clash {
+            ui.label(format!("That key is already used for \"{}\".", clash.label()));
+        }
+
+        if ui.button("Reset to defaults").clicked() {
+            self.shortcuts = default_shortcuts();
+            self.rebinding = None;
+            self.clash = None;
+        }
+    }
+
+    // black at the bottom. auto-flip turns the board from wherever it was flipped to by hand
+    fn flipped(&self) -> bool {
+        self.flip != (self.auto_flip && self.opponent.is_none() && self.game.to_play == board::Color::Black)
+    }
+
+    // rebuild the square and piece shapes, returns false if some piece textures are still loading.
+    // turn goes from 0 (white at the bottom) to 1 (black at the bottom), in between the board is part way round
+    fn build_board_shapes(&mut self, ctx: &egui::Context, board_rect: Rect, turn: f32) -> bool {
+        let sq_size = board_rect.width() / (self.game.shape.1 as f32);
+        let mut complete = true;
+
+        self.board_shapes.clear();
+
+        for j in 0..self.game.shape.1 {
+            for i in 0..self.game.shape.0 {
+                let index = i*self.game.shape.1 + j;
+                let square = &self.game.squares[index];
+                let square_color = match (i^j)&1 {
+                    0 => Self::LIGHT_SQ_COLOR,
+                    1 => Self::DARK_SQ_COLOR,
+                    _ => panic!("wtf..."),
+                };
+
+                let thisrect = egui::Rect{
+                    min: egui::Pos2{x: (j as f32) * sq_size + board_rect.min.x, y: (i as f32) * sq_size + board_rect.min.y},
+                    max: egui::Pos2{x: ((j as f32)+1.) * sq_size + board_rect.min.x, y: ((i as f32)+1.) * sq_size + board_rect.min.y},
+                };
+                // swing the square around the board centre, pieces stay upright
+                let offset = egui::emath::Rot2::from_angle(turn * std::f32::consts::PI) * (thisrect.center() - board_rect.center());
+                let thisrect = Rect::from_center_size(board_rect.center() + offset, thisrect.size());
+
+                self.board_shapes.push(Shape::rect_filled(thisrect, 0.0, square_color));
+
+                if self.selected == Some(index) {
+                    self.board_shapes.push(Shape::rect_filled(thisrect, 0.0, Self::SELECTED_SQ_COLOR));
+                }
+
+                if let Some(s) = self.piece_assets.get(&(square.color, square.piece)) {
+                    match s.load_for_size(ctx, thisrect.size()) {
+                        Ok(egui::load::TexturePoll::Ready { texture }) => self.board_shapes.push(Shape::image(
+                            texture.id,
+                            thisrect,
+                            Rect::from_min_max(pos2(0., 0.), pos2(1., 1.)),
+                            Color32::WHITE,
+                        )),
+                        Ok(egui::load::TexturePoll::Pending { .. }) => complete = false,
+                        // won't load however long we wait, leave the square without its piece
+                        Err(_) => (),
+                    }
+                } else if square.piece != board::PieceType::Empty {
+                    // a fairy without artwork gets its FEN letter
+                    let (letter, color) = match square.color {
+                        board::Color::White => (self.game.letter(square.piece), Color32::WHITE),
+                        board::Color::Black => (self.game.letter(square.piece).to_ascii_lowercase(), Color32::BLACK),
+                    };
+                    let font = egui::FontId::proportional(sq_size * 0.6);
+                    self.board_shapes.push(ctx.fonts(|fonts| {
+                        Shape::text(fonts, thisrect.center(), egui::Align2::CENTER_CENTER, letter, font, color)
+                    }));
+                }
+
+                if self.selected_moves.iter().any(|m| m.to == index) {
+                    self.board_shapes.push(Shape::circle_filled(thisrect.center(), sq_size * 0.15, Self::MOVE_HINT_COLOR));
+                }
+            }
+        }
+
+        complete
+    }
+
+    fn square_at(&self, board_rect: Rect, pos: egui::Pos2) -> Option<usize> {
+        if !board_rect.contains(pos) {
+            return None;
+        }
+
+        let sq_size = board_rect.width() / (self.game.shape.1 as f32);
+        let file = (((pos.x - board_rect.min.x) / sq_size) as usize).min(self.game.shape.1 - 1);
+        let rank = (((pos.y - board_rect.min.y) / sq_size) as usize).min(self.game.shape.0 - 1);
+        let index = rank * self.game.shape.1 + file;
+
+        match self.flipped() {
+            true => Some(self.game.squares.len() - 1 - index),
+            false => Some(index),
+        }
+    }
+
+    // square name, occupant and how many pieces of each side bear on it
+    fn square_tooltip(&self, ui: &mut egui::Ui, index: usize) {
+        let square = self.game.squares[index];
+        let white = self.game.attackers_of(index, board::Color::White).count_ones();
+        let black = self.game.attackers_of(index, board::Color::Black).count_ones();
+
+        ui.strong(self.game.index_to_alg(index));
+
+        if square.piece == board::PieceType::Empty {
+            ui.label("empty");
+            ui.label(format!("attacked by {} white, {} black", white, black));
+        } else {
+            let (defenders, attackers) = match square.color {
+                board::Color::White => (white, black),
+                board::Color::Black => (black, white),
+            };
+            ui.label(format!("{:?} {:?}", square.color, square.piece).to_lowercase());
+            ui.label(format!("attacked {}x, defended {}x", attackers, defenders));
+        }
+    }
+
+    fn load_position(&mut self, position: board::Board) {
+        self.load_game(game::Game::new(position));
+    }
+
+    fn load_game(&mut self, game: game::Game) {
+        self.game = game;
+        self.load_fairy_assets();
+        self.screened = false;
+        self.selected = None;
+        self.selected_moves.clear();
+    }
+
+    fn play_opponent(&mut self) {
+        // not while looking back through the game either, a move there would cut off the rest
+        if self.game.to_play != board::Color::Black || self.game.termination().is_some()
+            || self.game.ply() < self.game.plies().len() {
+            return;
+        }
+
+        if let Some(m) = self.opponent.as_mut().and_then(|bot| bot.choose_move(&self.game)) {
+            let _ = self.game.play(m); // bots pick from the legal move list
+            self.selected = None;
+            self.selected_moves.clear();
+        }
+    }
+
+    // the playouts take a second or more, so they run off the UI thread
+    fn start_estimate(&mut self) {
+        let position = self.game.board().clone();
+        let start = position.clone();
+        let worker = std::thread::spawn(move || bots::estimate(&start, 200, 60, &mut bots::Rng::from_time()));
+        self.estimating = Some((position, worker));
+    }
+
+    // pick up the estimate once the worker is done, until then keep frames coming to check on it
+    fn poll_estimate(&mut self, ctx: &egui::Context) {
+        match &self.estimating {
+            Some((_, worker)) if worker.is_finished() => {
+                if let Some((position, worker)) = self.estimating.take() {
+                    if let Ok(estimate) = worker.join() {
+                        self.estimate = Some((position, estimate));
+                    }
+                }
+            },
+            Some(_) => ctx.request_repaint_after(Self::ESTIMATE_POLL_INTERVAL),
+            None => (),
+        }
+    }
+
+    fn opponent_menu(&mut self, ui: &mut egui::Ui) {
+        if ui.button("Human").clicked() {
+            self.opponent = None;
+            ui.close_menu();
+        }
+
+        let bots: [Box<dyn bots::Bot>; 3] = [
+            Box::new(bots::RandomMover::new(bots::Rng::from_time())),
+            Box::new(bots::GreedyCapture::new(bots::Rng::from_time())),
+            Box::new(bots::OnePlyBot::new(eval::Material, bots::Rng::from_time())),
+        ];
+        for bot in bots {
+            if ui.button(bot.name()).clicked() {
+                self.opponent = Some(bot);
+                ui.close_menu();
+            }
+        }
+    }
+
+    // Ctrl+V anywhere in the window: keep the text if it parses as a FEN, or a PGN game (its final position),
+    // so it can be offered for loading
+    fn check_paste(&mut self, ctx: &egui::Context) {
+        let pasted = ctx.input(|i| i.events.iter().find_map(|event| match event {
+            egui::Event::Paste(text) => Some(text.clone()),
+            _ => None,
+        }));
+
+        if let Some(text) = pasted {
+            self.pasted = detect_paste(&text);
+        }
+    }
+
+    // the game's SAN, a row per move. clicking a move goes to the position after it
+    fn move_list(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Moves");
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.notation, notation::PieceStyle::Letter, "Letters");
+            ui.selectable_value(&mut self.notation, notation::PieceStyle::Figurine, "Figurines")
+                .on_hover_text("Figurine algebraic notation, ♘f3 for Nf3");
+        });
+        if ui.selectable_label(self.game.ply() == 0, "Start").clicked() {
+            self.browse(|game| game.goto(0));
+        }
+
+        let start = self.game.start();
+        let (first_number, black_first) = (start.fullmove_number as usize, start.to_play == board::Color::Black);
+        let mut clicked = None;
+        egui::ScrollArea::vertical().auto_shrink([false, true]).show(ui, |ui| {
+            egui::Grid::new("moves").num_columns(3).striped(true).show(ui, |ui| {
+                // a game set up with black to move starts its first row at "1..."
+                let offset = black_first as usize;
+                for row in 0..(self.game.plies().len() + offset).div_ceil(2) {
+                    ui.label(format!("{}.", first_number + row));
+                    for ply in [2 * row, 2 * row + 1] {
+                        match ply.checked_sub(offset).and_then(|i| self.game.plies().get(i).map(|p| (i, p))) {
+                            Some((i, p)) => {
+                                let text = match self.notation {
+                                    notation::PieceStyle::Letter => &p.san,
+                                    notation::PieceStyle::Figurine => &p.fan,
+                                };
+                                if ui.selectable_label(self.game.ply() == i + 1, text).clicked() {
+                                    clicked = Some(i + 1);
+                                }
+                            },
+                            None => {
+                                ui.label(if ply < offset { "..." } else { "" });
+                            },
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+
+        if let Some(ply) = clicked {
+            self.browse(|game| game.goto(ply));
+        }
+    }
+
+    // click-to-move: first click picks up one of our pieces, second click on a highlighted square plays it
+    fn click_square(&mut self, index: usize) {
+        let square = self.game.squares[index];
+
+        if let Some(m) = self.selected_moves.iter().find(|m| m.to == index).copied() {
+            let _ = self.game.play(m); // generated by legal_moves_from, can't be rejected
+            self.selected = None;
+            self.selected_moves.clear();
+            self.screened = self.privacy && self.opponent.is_none() && self.game.termination().is_none();
+        } else if square.piece != board::PieceType::Empty && square.color == self.game.to_play && self.selected != Some(index)
+               && self.game.termination().is_none() {
+            self.selected = Some(index);
+            self.selected_moves = self.game.legal_moves_from(index);
+        } else {
+            self.selected = None;
+            self.selected_moves.clear();
+        }
+    }
 }
 
 impl eframe::App for ChessGUI {
 
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        let shortcuts: Vec<(String, egui::KeyboardShortcut)> = self.shortcuts.iter()
+            .map(|(action, shortcut)| (action.key().to_string(), *shortcut))
+            .collect();
+        eframe::set_value(storage, Self::SHORTCUTS_KEY, &shortcuts);
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // the moves would give away the position the privacy screen hides
+        if !self.screened {
+            egui::SidePanel::right("move list").default_width(200.).show(ctx, |ui| self.move_list(ui));
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             let total_window = ui.available_size();
-            ui.heading(match self.game.to_play {
-                board::Color::White => "White to play...",
-                board::Color::Black => "Black to play..."
+            match self.game.termination() {
+                Some(termination) => ui.heading(termination.to_string()),
+                None => ui.heading(match self.game.to_play {
+                    board::Color::White => "White to play...",
+                    board::Color::Black => "Black to play..."
+                }),
+            };
+
+            self.handle_shortcuts(ctx);
+            self.play_opponent();
+
+            ui.horizontal(|ui| {
+                ui.menu_button("New game", |ui| {
+                    if ui.button("Standard").clicked() {
+                        self.load_position(board::Board::from_fen(board::START_FEN).unwrap());
+                        ui.close_menu();
+                    }
+                    if ui.button("Capablanca (10x8)").clicked() {
+                        self.load_position(board::Board::from_fen(board::CAPABLANCA_FEN).unwrap());
+                        ui.close_menu();
+                    }
+
+                    // odds are given by black, the side an engine would take
+                    for (label, odds) in [
+                        ("Knight odds", board::Odds::Knight),
+                        ("Rook odds", board::Odds::Rook),
+                        ("Queen odds", board::Odds::Queen),
+                    ] {
+                        if ui.button(label).clicked() {
+                            self.load_position(board::Board::odds_start(board::Color::Black, odds));
+                            ui.close_menu();
+                        }
+                    }
+                });
+
+                if ui.add_enabled(self.game.ply() > 0, egui::Button::new("Undo")).clicked() {
+                    self.step(game::Game::undo);
+                }
+                if ui.add_enabled(self.game.ply() < self.game.plies().len(), egui::Button::new("Redo")).clicked() {
+                    self.step(game::Game::redo);
+                }
+                if ui.add_enabled(self.game.claimable_draw().is_some(), egui::Button::new("Claim draw"))
+                    .on_hover_text("Threefold repetition, or 50 moves each without a capture or pawn move").clicked() {
+                    self.game.claim_draw();
+                }
+
+                let opponent = match &self.opponent {
+                    Some(bot) => format!("Opponent: {}", bot.name()),
+                    None => "Opponent: Human".to_string(),
+                };
+                ui.menu_button(opponent, |ui| self.opponent_menu(ui));
+                if ui.button("Flip").clicked() {
+                    self.flip = !self.flip;
+                }
+                ui.add_enabled(self.opponent.is_none(), egui::Checkbox::new(&mut self.auto_flip, "Auto-flip"))
+                    .on_hover_text("Turn the board to face the side to move");
+                ui.add_enabled(self.opponent.is_none(), egui::Checkbox::new(&mut self.privacy, "Privacy screen"))
+                    .on_hover_text("Hide the board between moves until the next player is ready");
+
+                ui.menu_button("Export", |ui| {
+                    let exports = [
+                        ("Copy FEN", self.game.to_fen()),
+                        ("Copy PGN", self.game.to_pgn().to_string()),
+                        ("Copy PGN with figurines", self.game.to_pgn().to_string_styled(notation::PieceStyle::Figurine)),
+                        ("Copy ASCII diagram", diagram::text(&self.game, notation::PieceStyle::Letter)),
+                        ("Copy Unicode diagram", diagram::text(&self.game, notation::PieceStyle::Figurine)),
+                        ("Copy LaTeX diagram", diagram::latex(&self.game)),
+                    ];
+                    for (label, text) in exports {
+                        if ui.button(label).clicked() {
+                            ui.output_mut(|o| o.copied_text = text);
+                            ui.close_menu();
+                        }
+                    }
+                });
+
+                ui.menu_button("Shortcuts", |ui| self.shortcuts_menu(ui));
+
+                self.poll_estimate(ctx);
+                if ui.add_enabled(self.estimating.is_none(), egui::Button::new("Estimate"))
+                    .on_hover_text("Quick guess from 200 greedy playouts").clicked() {
+                    self.start_estimate();
+                }
+                match (&self.estimating, &self.estimate) {
+                    (Some(_), _) => {
+                        ui.spinner();
+                    },
+                    (None, Some((position, estimate))) if position == self.game.board() => {
+                        ui.label(format!("White {:.0}%, draw {:.0}%, black {:.0}%",
+                            100.0 * estimate.white as f32 / estimate.playouts() as f32,
+                            100.0 * estimate.draws as f32 / estimate.playouts() as f32,
+                            100.0 * estimate.black as f32 / estimate.playouts() as f32));
+                    },
+                    _ => (),
+                }
             });
 
+            self.check_paste(ctx);
+            if let Some((kind, _)) = self.pasted {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Clipboard holds {}.", kind));
+                    if ui.button("Load").clicked() {
+                        if let Some((_, game)) = self.pasted.take() {
+                            self.load_game(game);
+                        }
+                    }
+                    if ui.button("Dismiss").clicked() {
+                        self.pasted = None;
+                    }
+                });
+            }
+
             ui.separator();
 
+            // pass the device: nothing of the position is drawn until the player to move says so
+            if self.screened {
+                ui.vertical_centered(|ui| {
+                    ui.add_space(ui.available_height() / 3.);
+                    ui.heading(format!("Pass the device to {:?}", self.game.to_play));
+                    if ui.button("Ready").clicked() {
+                        self.screened = false;
+                    }
+                });
+                return;
+            }
+
             let draw_window = ui.available_size();
 
-            let painter = egui::Painter::new(ctx.clone(), egui::layers::LayerId::new(
-                egui::layers::Order::Foreground,
-                egui::Id::new("master painter")),
-                egui::Rect::from_min_size(egui::Pos2::ZERO, draw_window)
+
+            let (ranks, files) = (self.game.shape.0 as f32, self.game.shape.1 as f32);
+            let sq_size = f32::min(Self::DEF_SQ_SIZE, f32::min(draw_window.x/files, draw_window.y/ranks));
+
+            let x_pad = if draw_window.x/files < Self::DEF_SQ_SIZE {
+                0.
+            } else {
+                (draw_window.x - (files*sq_size)) / 2.
+            };
+
+            let y_pad = total_window.y - draw_window.y;
+
+            let board_rect = egui::Rect::from_min_size(
+                pos2(x_pad, y_pad),
+                egui::Vec2{x: (self.game.shape.1 as f32) * sq_size, y: (self.game.shape.0 as f32) * sq_size},
             );
 
+            let response = ui.interact(board_rect, egui::Id::new("board"), egui::Sense::click());
+            if response.clicked() {
+                if let Some(index) = response.interact_pointer_pos().and_then(|pos| self.square_at(board_rect, pos)) {
+                    self.click_square(index);
+                    if self.opponent.is_some() {
+                        ctx.request_repaint(); // let the bot reply on the next frame
+                    }
+                }
+            }
+
+            if let Some(index) = response.hover_pos().and_then(|pos| self.square_at(board_rect, pos)) {
+                response.on_hover_ui_at_pointer(|ui| self.square_tooltip(ui, index));
+            }
+
+            let turn = ctx.animate_bool_with_time(egui::Id::new("board flip"), self.flipped(), Self::FLIP_TIME);
 
-            let sq_size = f32::min(Self::DEF_SQ_SIZE, f32::min(draw_window.x/8., draw_window.y/8.));
+            // only rebuild the board when the position, layout, selection or orientation changed since the last frame
+            let up_to_date = match &self.drawn {
+                Some((squares, rect, selected, drawn_turn)) => *squares == self.game.squares && *rect == board_rect && *selected == self.selected && *drawn_turn == turn,
+                None => false,
+            };
 
-            let x_pad = (|x: &f32| {
-                if x < &Self::DEF_SQ_SIZE {
-                    0.
+            if !up_to_date {
+                if self.build_board_shapes(ctx, board_rect, turn) {
+                    self.drawn = Some((self.game.squares.clone(), board_rect, self.selected, turn));
                 } else {
-                    (draw_window.x - (8.*x)) / 2.
+                    // svg textures are rasterized lazily, come back once they are ready
+                    self.drawn = None;
+                    ctx.request_repaint_after(Self::TEXTURE_POLL_INTERVAL);
                 }
-            })(&(draw_window.x/8.));
+            }
 
-            let y_pad = total_window.y - draw_window.y;
+            // the panel's own layer, so menus and tooltips open on top of the board
+            ui.painter().extend(self.board_shapes.iter().cloned());
+        });
+    }
+}
 
-            for j in 0..self.game.shape.1 {
-                for i in 0..self.game.shape.0 {
-                    let index = i*self.game.shape.1 + j;
-                    let square = &self.game.squares[index];
-                    let square_color = match (i^j)&1 {
-                        0 => Self::LIGHT_SQ_COLOR,
-                        1 => Self::DARK_SQ_COLOR,
-                        _ => panic!("wtf..."),
-                    };
+#[cfg(test)]
+mod tests {
 
-                    let thisrect = egui::Rect{
-                        min: egui::Pos2{x: (j as f32) * sq_size + x_pad, y: (i as f32) * sq_size + y_pad},
-                        max: egui::Pos2{x: ((j as f32)+1.) * sq_size + x_pad, y: ((i as f32)+1.) * sq_size + y_pad},
-                    };
+    use eframe::egui::{Key, KeyboardShortcut, Modifiers};
+    use crate::gui::*;
+    #[test]
+    fn shortcuts_test() {
+        let key = |k| KeyboardShortcut::new(Modifiers::NONE, k);
+        let saved = |pairs: &[(&str, KeyboardShortcut)]| pairs.iter().map(|(name, s)| (name.to_string(), *s)).collect::<Vec<_>>();
 
-                    painter.rect_filled(thisrect, 0.0, square_color);
+        // saved keys over the defaults, unknown names dropped
+        let shortcuts = restore_shortcuts(saved(&[("flip", key(Key::G)), ("gone", key(Key::H))]));
+        assert_eq!(shortcuts.len(), default_shortcuts().len());
+        assert!(shortcuts.contains(&(Action::Flip, key(Key::G))));
+        assert!(shortcuts.contains(&(Action::Deselect, key(Key::Escape))));
+        assert_eq!(restore_shortcuts(Vec::new()), default_shortcuts());
 
+        // two actions on one key is the defaults again, two swapped keys are fine
+        assert_eq!(restore_shortcuts(saved(&[("flip", key(Key::Escape))])), default_shortcuts());
+        let swapped = restore_shortcuts(saved(&[("flip", key(Key::Escape)), ("deselect", key(Key::F))]));
+        assert!(swapped.contains(&(Action::Flip, key(Key::Escape))) && swapped.contains(&(Action::Deselect, key(Key::F))));
 
-                    match &self.piece_assets.get(&(square.color, square.piece)) {
-                        Some(s) => s
-                            .max_width(sq_size)
-                            .paint_at(ui, thisrect),
-                        _ => (),
-                    };
-                } 
-            }
-        });
+        // a rebind onto another action's key is refused and leaves the bindings alone
+        let mut shortcuts = default_shortcuts();
+        assert_eq!(rebind(&mut shortcuts, Action::Flip, key(Key::Escape)), Err(Action::Deselect));
+        assert_eq!(shortcuts, default_shortcuts());
+        assert_eq!(rebind(&mut shortcuts, Action::Flip, key(Key::F)), Ok(()));
+        assert_eq!(rebind(&mut shortcuts, Action::Flip, key(Key::G)), Ok(()));
+        assert_eq!(clash(&shortcuts, Action::Undo, key(Key::G)), Some(Action::Flip));
+        assert_eq!(clash(&shortcuts, Action::Flip, key(Key::G)), None);
+        assert_eq!(clash(&shortcuts, Action::Undo, key(Key::F)), None);
+    }
+
+    #[test]
+    fn detect_paste_test() {
+        let found = |text| detect_paste(text).map(|(kind, _)| kind);
+
+        let (kind, game) = detect_paste("  4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1\n").unwrap();
+        assert_eq!((kind, game.to_fen().as_str()), ("a FEN position", "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1"));
+        assert_eq!(found("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq -"), Some("a FEN position"));
+
+        // en passant squares that used to panic in from_fen, and positions no game reaches
+        for text in [
+            "4k3/8/8/4P3/8/8/8/4K3 w - d6 0 1",
+            "4k3/8/8/3nP3/8/8/8/4K3 w - d6 0 1",
+            "4k3/8/8/8/3P4/8/8/4K3 b - d4 0 1",
+            "4k3/8/8/8/3P4/3P4/8/4K3 b - d3 0 1",
+            "4k3/8/8/8/3p4/8/8/4K3 w - d5 0 1",
+            "4k3/8/8/8/8/8/8/8 w - - 0 1",
+            "4k3/8/8/8/4R3/8/8/4K3 w - - 0 1",
+        ] {
+            assert_eq!(found(text), None, "{}", text);
+        }
+
+        let (kind, game) = detect_paste("[Event \"Casual\"]\n\n1. e4 e5 2. Nf3 *\n").unwrap();
+        assert_eq!((kind, game.plies().len()), ("a PGN game", 3));
+        assert_eq!(found("1. e4 e5 1-0"), Some("a PGN game"));
+        assert_eq!(found("[Event \"Casual\"]\n\n*"), None);
+        assert_eq!(found("1. e4 e5 2. Ke3"), None);
+        assert_eq!(found("hello there"), None);
+        assert_eq!(found(""), None);
     }
 }
@@ -0,0 +1,14 @@
+pub mod board;
+pub mod diagram;
+pub mod bots;
+pub mod eval;
+pub mod fairy;
+pub mod game;
+pub mod gui;
+pub mod notation;
+pub mod pgn;
+pub mod uci;
+
+#[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+mod pext;
+mod zobrist;
@@ -2,16 +2,16 @@ use rust_chess::gui;
 
 fn main() -> Result<(), eframe::Error> {
     let options = eframe::NativeOptions {
-        viewport: {
-            Some(egui::Vec2{x: 1000.0, y: 700.0}),
-            ..Default::default()
-        },
+        viewport: egui::ViewportBuilder::default().with_inner_size([1000.0, 700.0]),
         ..Default::default()
     };
     eframe::run_native(
         "rust_chess",
         options,
-        Box::new(|_cc| Box::new(gui::ChessGUI::default())),
+        Box::new(|cc| {
+            egui_extras::install_image_loaders(&cc.egui_ctx); // the piece svgs
+            Ok(Box::new(gui::ChessGUI::new(cc)))
+        }),
     )
     
 }
@@ -0,0 +1,355 @@
+// Move text: standard algebraic notation (SAN), its figurine flavour (FAN) and
+// long algebraic notation (LAN).
+use crate::board::{Board, Color, MoveOp, PieceType, PIECE_MAP, PIECE_TYPES};
+use regex::Regex;
+use lazy_static::lazy_static;
+
+// by color: outline glyphs for white's moves, solid ones for black's. Unicode has no
+// archbishop or chancellor, those keep their letters
+pub const FIGURINE_MAP: [[char; PIECE_TYPES]; 2] = [
+    ['.', '♙', '♖', '♘', '♗', '♕', '♔', 'A', 'C'],
+    ['.', '♟', '♜', '♞', '♝', '♛', '♚', 'A', 'C'],
+];
+
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+pub enum SanError {
+    Malformed,  // not SAN at all
+    NoMatch,    // well formed, but no legal move fits
+    Ambiguous,  // more than one legal move fits, disambiguation missing
+}
+
+#[derive(Debug,Copy,Clone,Eq,PartialEq,Default)]
+pub enum PieceStyle {
+    #[default] Letter,
+               Figurine,
+}
+
+// SAN for a legal move in board's position, e.g. "Nbd2", "exd5", "e8=Q", "O-O"
+pub fn san(board: &Board, moveop: &MoveOp) -> String {
+    styled_san(board, moveop, PieceStyle::Letter)
+}
+
+// figurine algebraic notation, "♘f3" instead of "Nf3", "♞f6" for black
+pub fn fan(board: &Board, moveop: &MoveOp) -> String {
+    styled_san(board, moveop, PieceStyle::Figurine)
+}
+
+fn piece_char(board: &Board, style: PieceStyle, piece: PieceType, color: Color) -> char {
+    match style {
+        PieceStyle::Letter   => board.letter(piece),
+        PieceStyle::Figurine => FIGURINE_MAP[color as usize].get(piece.index()).copied().unwrap_or(board.letter(piece)), // fairies keep their letters
+    }
+}
+
+// the letter a figurine of either color stands for, so FAN reads back like SAN
+fn figurine_letter(c: char) -> Option<char> {
+    FIGURINE_MAP.iter().find_map(|glyphs| glyphs.iter().position(|&g| g == c)).map(|i| PIECE_MAP[i])
+}
+
+pub fn styled_san(board: &Board, moveop: &MoveOp, style: PieceStyle) -> String {
+    let mover = board.squares[moveop.from];
+    let target = board.index_to_alg(moveop.to);
+    let is_capture = moveop.is_enpassant || board.squares[moveop.to].piece != PieceType::Empty;
+
+    if moveop.is_castle {
+        return if moveop.to > moveop.from { "O-O".to_string() } else { "O-O-O".to_string() };
+    }
+
+    let mut text = String::new();
+
+    if mover.piece == PieceType::Pawn {
+        if is_capture {
+            text.push_str(&board.index_to_alg(moveop.from)[..1]);
+        }
+    } else {
+        text.push(piece_char(board, style, mover.piece, mover.color));
+        text.push_str(&disambiguation(board, moveop));
+    }
+
+    if is_capture {
+        text.push('x');
+    }
+    text.push_str(&target);
+
+    if moveop.promote != PieceType::Empty {
+        text.push('=');
+        text.push(piece_char(board, style, moveop.promote, mover.color));
+    }
+
+    text
+}
+
+// long algebraic notation, origin always spelled out: "Ng1-f3", "e7xd8=Q", "O-O"
+pub fn lan(board: &Board, moveop: &MoveOp) -> String {
+    let mover = board.squares[moveop.from];
+    let is_capture = moveop.is_enpassant || board.squares[moveop.to].piece != PieceType::Empty;
+
+    if moveop.is_castle {
+        return if moveop.to > moveop.from { "O-O".to_string() } else { "O-O-O".to_string() };
+    }
+
+    let mut text = String::new();
+    if mover.piece != PieceType::Pawn {
+        text.push(board.letter(mover.piece));
+    }
+    text.push_str(&board.index_to_alg(moveop.from));
+    text.push(if is_capture { 'x' } else { '-' });
+    text.push_str(&board.index_to_alg(moveop.to));
+
+    if moveop.promote != PieceType::Empty {
+        text.push('=');
+        text.push(board.letter(moveop.promote));
+    }
+
+    text
+}
+
+// the legal move spelled by a LAN string, check/annotation suffixes are ignored
+pub fn parse_lan(board: &Board, text: &str) -> Option<MoveOp> {
+    let text = text.trim().trim_end_matches(['+', '#', '!', '?']);
+
+    if matches!(text, "O-O" | "0-0" | "O-O-O" | "0-0-0") {
+        return castle_move(board, text.len() == 3);
+    }
+
+    let (piece, text) = match text.chars().next().filter(|c| c.is_ascii_uppercase()) {
+        Some(c) => (board.piece_from_letter(c)?, &text[1..]),
+        None => (PieceType::Pawn, text),
+    };
+
+    let (from, rest) = text.split_once(['-', 'x'])?;
+    let (to, promote) = match rest.split_once('=') {
+        Some((to, promote)) if promote.len() == 1 => (to, board.piece_from_letter(promote.chars().next()?)?),
+        Some(_) => return None,
+        None => (rest, PieceType::Empty),
+    };
+
+    let from = board.parse_square(from)?;
+    let to = board.parse_square(to)?;
+    if board.squares[from].piece != piece {
+        return None;
+    }
+
+    board.clone().legal_moves_from(from).iter()
+        .find(|m| m.to == to && m.promote == promote)
+        .copied()
+}
+
+fn castle_move(board: &Board, kingside: bool) -> Option<MoveOp> {
+    let king = board.squares.iter().position(|s| s.piece == PieceType::King && s.color == board.to_play)?;
+    board.clone().legal_moves_from(king).iter()
+        .find(|m| m.is_castle && (m.to > m.from) == kingside)
+        .copied()
+}
+
+impl Board {
+    // SAN as it goes in a game score, with '+' or '#' after checking moves
+    pub fn san(&self, moveop: &MoveOp) -> String {
+        self.san_styled(moveop, PieceStyle::Letter)
+    }
+
+    // the same with the pieces in style, FAN for figurines
+    pub fn san_styled(&self, moveop: &MoveOp, style: PieceStyle) -> String {
+        let mut text = styled_san(self, moveop, style);
+        if self.gives_check(moveop) {
+            let mated = self.apply_move_nomut(*moveop).get_legal_moves().is_empty();
+            text.push(if mated { '#' } else { '+' });
+        }
+
+        text
+    }
+
+    // the legal move a SAN string stands for. lenient about what people actually type:
+    // check and annotation marks, "e.p.", a missing 'x' or '=', zeros for castling, figurines
+    pub fn parse_san(&self, text: &str) -> Result<MoveOp, SanError> {
+        lazy_static!{
+            // the origin file is lazy, or the 'x' of "Nxe5" would be read as one
+            static ref SAN_EXP: Regex = Regex::new(r"^([A-Z])?([a-z])??(\d+)?x?([a-z]\d+)=?([A-Z])?$").unwrap();
+        }
+
+        let text = text.chars().map(|c| figurine_letter(c).unwrap_or(c)).collect::<String>();
+        let text = text.trim().trim_end_matches("e.p.").trim_end().trim_end_matches(['+', '#', '!', '?']);
+
+        if matches!(text, "O-O" | "0-0" | "O-O-O" | "0-0-0") {
+            return castle_move(self, text.len() == 3).ok_or(SanError::NoMatch);
+        }
+
+        let fields = SAN_EXP.captures(text).ok_or(SanError::Malformed)?;
+        let letter = |i: usize| fields.get(i).map(|m| m.as_str().chars().next().unwrap());
+
+        // any piece letter, fairies' included
+        let piece = match letter(1) {
+            Some(c) => self.piece_from_letter(c).ok_or(SanError::Malformed)?,
+            None => PieceType::Pawn,
+        };
+        let promote = match letter(5) {
+            Some(c) => self.piece_from_letter(c).ok_or(SanError::Malformed)?,
+            None => PieceType::Empty,
+        };
+        let file = letter(2).map(|c| c as usize - 'a' as usize);
+        let rank = fields.get(3).map(|m| m.as_str().parse::<usize>()).transpose().map_err(|_| SanError::Malformed)?;
+        let to = self.parse_square(&fields[4]).ok_or(SanError::Malformed)?;
+
+        let width = self.shape.1;
+        let moves = self.clone().get_legal_moves();
+        let mut candidates = moves.iter().copied().filter(|m| {
+            self.squares[m.from].piece == piece && m.to == to && m.promote == promote && !m.is_castle
+                && file.is_none_or(|f| m.from % width == f)
+                && rank.is_none_or(|r| self.shape.0 - m.from / width == r)
+        });
+
+        match (candidates.next(), candidates.next()) {
+            (Some(m), None) => Ok(m),
+            (None, _) => Err(SanError::NoMatch),
+            (Some(_), Some(_)) => Err(SanError::Ambiguous),
+        }
+    }
+}
+
+// origin file, rank or both, whichever is the first to tell the move apart from the
+// other pieces of the same kind that could legally go to the same square (PGN spec 8.2.3.4)
+fn disambiguation(board: &Board, moveop: &MoveOp) -> String {
+    let width = board.shape.1;
+    let mover = board.squares[moveop.from];
+    let mut trial = board.clone(); // for trying out the rivals' moves
+
+    let rivals: Vec<usize> = (0..board.squares.len())
+        .filter(|&index| index != moveop.from && board.squares[index] == mover)
+        .filter(|&index| trial.legal_moves_from(index).iter().any(|m| m.to == moveop.to))
+        .collect();
+
+    if rivals.is_empty() {
+        return String::new();
+    }
+
+    let origin = board.index_to_alg(moveop.from);
+
+    if rivals.iter().all(|&index| index % width != moveop.from % width) {
+        origin[..1].to_string()
+    } else if rivals.iter().all(|&index| index / width != moveop.from / width) {
+        origin[1..].to_string()
+    } else {
+        origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::board::*;
+    use crate::notation::*;
+    #[test]
+    fn san_disambiguation_test() {
+        let mv = |from, to| MoveOp{from, to, ..Default::default()};
+
+        // knights on b1 and f3 both reach d2
+        let board = Board::from_fen("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1").unwrap();
+        assert_eq!(san(&board, &mv(57, 51)), "Nbd2");
+        assert_eq!(san(&board, &mv(45, 51)), "Nfd2");
+        assert_eq!(san(&board, &mv(45, 28)), "Ne5");
+
+        // rooks on e1 and e3 share the file
+        let board = Board::from_fen("4k3/8/8/8/8/4R3/8/K3R3 w - - 0 1").unwrap();
+        assert_eq!(san(&board, &mv(60, 52)), "R1e2");
+        assert_eq!(san(&board, &mv(44, 52)), "R3e2");
+
+        // queens on e4, h4 and h1 all reach e1
+        let board = Board::from_fen("k7/8/8/8/4Q2Q/8/8/K6Q w - - 0 1").unwrap();
+        assert_eq!(san(&board, &mv(39, 60)), "Qh4e1");
+        assert_eq!(san(&board, &mv(36, 60)), "Qee1");
+        assert_eq!(san(&board, &mv(63, 60)), "Q1e1");
+
+        // the c3 knight is pinned, so Ng1-e2 needs no disambiguation
+        let board = Board::from_fen("4k3/8/8/8/1b6/2N5/8/4K1N1 w - - 0 1").unwrap();
+        assert_eq!(san(&board, &mv(62, 52)), "Ne2");
+
+        // pawns, promotions and castling
+        let board = Board::from_fen("3rk3/4P3/8/3p4/4P3/8/8/4K2R w K - 0 1").unwrap();
+        assert_eq!(san(&board, &mv(36, 27)), "exd5");
+        assert_eq!(san(&board, &MoveOp{from: 12, to: 3, promote: PieceType::Knight, ..Default::default()}), "exd8=N");
+        assert_eq!(san(&board, &MoveOp{from: 60, to: 62, is_castle: true, ..Default::default()}), "O-O");
+    }
+
+    #[test]
+    fn fan_test() {
+        let board = Board::from_fen(START_FEN).unwrap();
+        assert_eq!(fan(&board, &MoveOp{from: 62, to: 45, ..Default::default()}), "♘f3");
+        assert_eq!(fan(&board, &MoveOp{from: 52, to: 36, ..Default::default()}), "e4");
+
+        let board = Board::from_fen("3rk3/4P3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let exd8 = MoveOp{from: 12, to: 3, promote: PieceType::Queen, ..Default::default()};
+        assert_eq!(fan(&board, &exd8), "exd8=♕");
+        assert_eq!(board.san_styled(&exd8, PieceStyle::Figurine), "exd8=♕+");
+        assert_eq!(board.parse_san("exd8=♕+"), Ok(exd8));
+
+        // black's pieces are solid
+        let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap();
+        let nf6 = board.parse_san("♞f6").unwrap();
+        assert_eq!(fan(&board, &nf6), "♞f6");
+        assert_eq!(board.parse_san("♘f6"), Ok(nf6));
+    }
+
+    #[test]
+    fn lan_test() {
+        let board = Board::from_fen(START_FEN).unwrap();
+        let nf3 = MoveOp{from: 62, to: 45, ..Default::default()};
+        assert_eq!(lan(&board, &nf3), "Ng1-f3");
+        assert_eq!(parse_lan(&board, "Ng1-f3"), Some(nf3));
+        assert_eq!(parse_lan(&board, "e2-e4").map(|m| lan(&board, &m)), Some("e2-e4".to_string()));
+        assert_eq!(parse_lan(&board, "Ng1-g3"), None);
+        assert_eq!(parse_lan(&board, "Bg1-f3"), None);
+        assert_eq!(parse_lan(&board, "e2-e9"), None);
+
+        let board = Board::from_fen("3rk3/4P3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(lan(&board, &MoveOp{from: 12, to: 3, promote: PieceType::Queen, ..Default::default()}), "e7xd8=Q");
+        assert_eq!(lan(&board, &MoveOp{from: 60, to: 59, ..Default::default()}), "Ke1-d1");
+    }
+
+    #[test]
+    fn parse_san_test() {
+        let mv = |m: Result<MoveOp, SanError>| m.map(|m| (m.from, m.to, m.promote));
+
+        let board = Board::from_fen("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1").unwrap();
+        assert_eq!(mv(board.parse_san("Nbd2")), Ok((57, 51, PieceType::Empty)));
+        assert_eq!(mv(board.parse_san("N3d2")), Ok((45, 51, PieceType::Empty)));
+        assert_eq!(mv(board.parse_san("Nf3xe5!?")), Ok((45, 28, PieceType::Empty)));
+        assert_eq!(mv(board.parse_san("Nxe5")), Ok((45, 28, PieceType::Empty)));
+        assert_eq!(board.parse_san("Nd2"), Err(SanError::Ambiguous));
+        assert_eq!(board.parse_san("Nd3"), Err(SanError::NoMatch));
+        assert_eq!(board.parse_san("Zz9"), Err(SanError::Malformed));
+
+        let board = Board::from_fen("r3k2r/1P6/8/3pP3/8/8/8/4K3 w kq d6 0 1").unwrap();
+        let exd6 = board.parse_san("exd6 e.p.").unwrap();
+        assert!(exd6.is_enpassant);
+        assert_eq!(board.parse_san("exd6"), Ok(exd6));
+        assert_eq!(mv(board.parse_san("b8=Q+")), Ok((9, 1, PieceType::Queen)));
+        assert_eq!(mv(board.parse_san("b8N")), Ok((9, 1, PieceType::Knight)));
+        assert_eq!(board.parse_san("b8"), Err(SanError::NoMatch));
+
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/4K3 b kq - 0 1").unwrap();
+        assert_eq!(mv(board.parse_san("O-O-O")), Ok((4, 2, PieceType::Empty)));
+        assert!(board.parse_san("0-0").unwrap().is_castle);
+
+        // every generated SAN reads back as the same move
+        let mut board = Board::from_fen(CAPABLANCA_FEN).unwrap();
+        for m in board.get_legal_moves().iter() {
+            assert_eq!(board.parse_san(&san(&board, m)), Ok(*m));
+        }
+    }
+
+    #[test]
+    fn san_suffix_test() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/1R4K1 w - - 0 1").unwrap();
+        assert_eq!(board.san(&MoveOp{from: 57, to: 1, ..Default::default()}), "Rb8#");
+        assert_eq!(board.san(&MoveOp{from: 57, to: 9, ..Default::default()}), "Rb7");
+
+        let board = Board::from_fen("6k1/5pp1/8/8/8/8/8/1R4K1 w - - 0 1").unwrap();
+        assert_eq!(board.san(&MoveOp{from: 57, to: 1, ..Default::default()}), "Rb8+");
+
+        // check by promotion, and the suffix reads back
+        let board = Board::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let promotion = MoveOp{from: 9, to: 1, promote: PieceType::Queen, ..Default::default()};
+        assert_eq!(board.san(&promotion), "b8=Q+");
+        assert_eq!(board.parse_san(&board.san(&promotion)), Ok(promotion));
+    }
+}
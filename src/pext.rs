@@ -0,0 +1,122 @@
+// PEXT indexed sliding attacks for the standard 8x8 board. Only built when the
+// target has BMI2, otherwise Board walks the rays itself.
+use std::arch::x86_64::_pext_u64;
+use lazy_static::lazy_static;
+
+use crate::board::{ROOK_DIRS, BISHOP_DIRS};
+
+struct SlidingTable {
+    masks: [u64; 64],
+    offsets: [usize; 64],
+    attacks: Vec<u64>,
+}
+
+impl SlidingTable {
+    fn new(dirs: &[(i16, i16)]) -> Self {
+        let mut table = SlidingTable {
+            masks: [0; 64],
+            offsets: [0; 64],
+            attacks: Vec::new(),
+        };
+
+        for square in 0..64 {
+            let mask = relevant_mask(square, dirs);
+            table.masks[square] = mask;
+            table.offsets[square] = table.attacks.len();
+            table.attacks.resize(table.attacks.len() + (1 << mask.count_ones()), 0);
+
+            // walk every subset of the mask (carry-rippler)
+            let mut subset: u64 = 0;
+            loop {
+                let index = table.offsets[square] + pext(subset, mask) as usize;
+                table.attacks[index] = ray_attacks(square, dirs, subset);
+
+                subset = subset.wrapping_sub(mask) & mask;
+                if subset == 0 {
+                    break;
+                }
+            }
+        }
+
+        table
+    }
+
+    fn lookup(&self, square: usize, occupied: u64) -> u64 {
+        self.attacks[self.offsets[square] + pext(occupied, self.masks[square]) as usize]
+    }
+}
+
+lazy_static! {
+    static ref ROOK_TABLE: SlidingTable = SlidingTable::new(&ROOK_DIRS);
+    static ref BISHOP_TABLE: SlidingTable = SlidingTable::new(&BISHOP_DIRS);
+}
+
+fn pext(value: u64, mask: u64) -> u64 {
+    // sound, this module is only compiled when the target has bmi2
+    unsafe { _pext_u64(value, mask) }
+}
+
+fn ray_attacks(square: usize, dirs: &[(i16, i16)], occupied: u64) -> u64 {
+    let mut attacks: u64 = 0;
+
+    for &(dr, df) in dirs {
+        let (mut rank, mut file) = ((square / 8) as i16 + dr, (square % 8) as i16 + df);
+        while (0..8).contains(&rank) && (0..8).contains(&file) {
+            let bit = 1 << (rank * 8 + file);
+            attacks |= bit;
+            if occupied & bit != 0 {
+                break;
+            }
+            rank += dr;
+            file += df;
+        }
+    }
+
+    attacks
+}
+
+// squares whose occupancy can change the attack set, i.e. the rays minus the edge they run into
+fn relevant_mask(square: usize, dirs: &[(i16, i16)]) -> u64 {
+    let mut mask: u64 = 0;
+
+    for &(dr, df) in dirs {
+        let (mut rank, mut file) = ((square / 8) as i16 + dr, (square % 8) as i16 + df);
+        while (0..8).contains(&(rank + dr)) && (0..8).contains(&(file + df)) {
+            mask |= 1 << (rank * 8 + file);
+            rank += dr;
+            file += df;
+        }
+    }
+
+    mask
+}
+
+pub fn rook_attacks(square: usize, occupied: u64) -> u64 {
+    ROOK_TABLE.lookup(square, occupied)
+}
+
+pub fn bishop_attacks(square: usize, occupied: u64) -> u64 {
+    BISHOP_TABLE.lookup(square, occupied)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::pext::*;
+    #[test]
+    fn pext_matches_rays() {
+        let mut seed: u64 = 0x9e3779b97f4a7c15;
+        for _ in 0..1000 {
+            // xorshift, sparse-ish occupancies
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let occupied = seed & seed.rotate_left(23);
+
+            for square in 0..64 {
+                assert_eq!(rook_attacks(square, occupied), ray_attacks(square, &ROOK_DIRS, occupied));
+                assert_eq!(bishop_attacks(square, occupied), ray_attacks(square, &BISHOP_DIRS, occupied));
+            }
+        }
+    }
+}
@@ -0,0 +1,516 @@
+// PGN: tag pairs, and games read and written with their variations, comments and NAGs.
+// The Seven Tag Roster is always present and written first, in the order the PGN spec (8.1.1) requires.
+use std::fmt;
+use std::str::FromStr;
+use crate::board::{Board, Color, GameResult, MoveOp, START_FEN};
+use crate::notation::{PieceStyle, SanError};
+
+pub const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+#[derive(Debug,Clone,Eq,PartialEq)]
+pub struct Tags {
+    roster: [String; 7],
+    extra: Vec<(String, String)>,
+}
+
+impl Default for Tags {
+    fn default() -> Self {
+        Tags {
+            roster: ["?", "?", "????.??.??", "?", "?", "?", "*"].map(String::from),
+            extra: Vec::new(),
+        }
+    }
+}
+
+impl Tags {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        match SEVEN_TAG_ROSTER.iter().position(|&t| t == name) {
+            Some(i) => Some(&self.roster[i]),
+            None => self.extra.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str()),
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) {
+        if let Some(i) = SEVEN_TAG_ROSTER.iter().position(|&t| t == name) {
+            self.roster[i] = value.to_string();
+        } else if let Some(pair) = self.extra.iter_mut().find(|(n, _)| n == name) {
+            pair.1 = value.to_string();
+        } else {
+            self.extra.push((name.to_string(), value.to_string()));
+        }
+    }
+
+    // roster tags can't be removed, only reset to their unknown value
+    pub fn remove(&mut self, name: &str) {
+        match SEVEN_TAG_ROSTER.iter().position(|&t| t == name) {
+            Some(i) => self.roster[i] = Tags::default().roster[i].clone(),
+            None => self.extra.retain(|(n, _)| n != name),
+        }
+    }
+
+    // any part of the date may be unknown, e.g. (Some(1972), None, None) -> "1972.??.??"
+    pub fn set_date(&mut self, year: Option<u16>, month: Option<u8>, day: Option<u8>) {
+        let year = year.map_or("????".to_string(), |y| format!("{:04}", y));
+        let month = month.map_or("??".to_string(), |m| format!("{:02}", m));
+        let day = day.map_or("??".to_string(), |d| format!("{:02}", d));
+
+        self.set("Date", &format!("{}.{}.{}", year, month, day));
+    }
+
+    // roster first, then the rest in ASCII order as the export format asks
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        let mut extra: Vec<(&str, &str)> = self.extra.iter().map(|(n, v)| (n.as_str(), v.as_str())).collect();
+        extra.sort();
+
+        SEVEN_TAG_ROSTER.iter().copied().zip(self.roster.iter().map(String::as_str)).chain(extra)
+    }
+}
+
+impl fmt::Display for Tags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in self.iter() {
+            writeln!(f, "[{} \"{}\"]", name, value.replace('\\', "\\\\").replace('"', "\\\""))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug,Clone,Eq,PartialEq)]
+pub enum PgnError {
+    BadTag(String),                 // a tag pair line that isn't [Name "value"]
+    BadFen(String),                 // the FEN tag doesn't parse
+    BadMove(usize, String, SanError), // ply (from 0), the SAN as written, why it didn't fit
+    UnbalancedVariation,            // a '(' without its ')' or the other way round
+}
+
+// one move of the game tree, with its annotations and the alternatives to it
+#[derive(Debug,Clone,Eq,PartialEq)]
+pub struct Node {
+    pub moveop: MoveOp,
+    pub nags: Vec<u8>,
+    pub before: Option<String>, // comment ahead of the move
+    pub after: Option<String>,  // comment following it
+    pub variations: Vec<Vec<Node>>, // lines played instead of this move, from the same position
+}
+
+impl Node {
+    pub fn new(moveop: MoveOp) -> Self {
+        Node { moveop, nags: Vec::new(), before: None, after: None, variations: Vec::new() }
+    }
+}
+
+// a game read from PGN: its tags, where it started and the main line with everything hanging off it
+#[derive(Clone)]
+pub struct PgnGame {
+    pub tags: Tags,
+    pub start: Board,
+    pub mainline: Vec<Node>,
+}
+
+impl PgnGame {
+    pub fn moves(&self) -> Vec<MoveOp> {
+        self.mainline.iter().map(|node| node.moveop).collect()
+    }
+
+    // position after the first ply moves of the main line
+    pub fn position_at(&self, ply: usize) -> Board {
+        let mut board = self.start.clone();
+        for node in self.mainline.iter().take(ply) {
+            let _ = board.try_move(node.moveop); // checked when the game was read
+        }
+
+        board
+    }
+
+    // final position, carrying the result from the tags if the moves don't end the game themselves
+    pub fn board(&self) -> Board {
+        let mut board = self.position_at(self.mainline.len());
+        if board.result == GameResult::Active {
+            if let Some(result) = GameResult::from_pgn(self.tags.get("Result").unwrap_or("*"), self.tags.get("Termination")) {
+                board.result = result;
+            }
+        }
+
+        board
+    }
+}
+
+// [Name "value"], with \" and \\ escapes in the value
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?.trim();
+    let (name, value) = inner.split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        unescaped.push(if c == '\\' { chars.next()? } else { c });
+    }
+
+    Some((name.to_string(), unescaped))
+}
+
+enum Token {
+    Move(String),
+    Nag(u8),
+    Comment(String),
+    Open,
+    Close,
+}
+
+// the traditional suffixes stand for the first six NAGs (PGN spec 10)
+const SUFFIX_NAGS: [&str; 6] = ["!", "?", "!!", "??", "!?", "?!"];
+
+// movetext to tokens, move numbers and the result are dropped
+fn tokenize(movetext: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = movetext.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => tokens.push(Token::Comment(chars.by_ref().take_while(|&c| c != '}').collect::<String>().trim().to_string())),
+            ';' => tokens.push(Token::Comment(chars.by_ref().take_while(|&c| c != '\n').collect::<String>().trim().to_string())),
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            c if c.is_whitespace() => (),
+            c => {
+                let mut token = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || "{};()".contains(next) {
+                        break;
+                    }
+                    token.push(next);
+                    chars.next();
+                }
+
+                // "12." and "12..." may be glued to the move: "12.e4"
+                if matches!(token.as_str(), "1-0" | "0-1" | "1/2-1/2" | "*") {
+                    continue;
+                }
+                let token = match token.find(|c: char| !c.is_ascii_digit()) {
+                    Some(i) if token[i..].starts_with('.') => token[i..].trim_start_matches('.'),
+                    _ => &token,
+                };
+
+                if let Some(nag) = token.strip_prefix('$') {
+                    if let Ok(nag) = nag.parse() {
+                        tokens.push(Token::Nag(nag));
+                    }
+                } else if !token.is_empty() {
+                    let san = token.trim_end_matches(['!', '?']);
+                    tokens.push(Token::Move(san.to_string()));
+                    if let Some(i) = SUFFIX_NAGS.iter().position(|&suffix| suffix == &token[san.len()..]) {
+                        tokens.push(Token::Nag(i as u8 + 1));
+                    }
+                }
+            },
+        }
+    }
+
+    tokens
+}
+
+fn append_comment(slot: &mut Option<String>, comment: String) {
+    *slot = Some(match slot.take() {
+        Some(earlier) => format!("{} {}", earlier, comment),
+        None => comment,
+    });
+}
+
+// moves from board until the end of the line: a ')' when nested, the end of the tokens otherwise
+fn parse_line(tokens: &mut impl Iterator<Item = Token>, start: &Board, first_ply: usize, nested: bool) -> Result<Vec<Node>, PgnError> {
+    let mut line: Vec<Node> = Vec::new();
+    let mut board = start.clone();
+    let mut previous = start.clone(); // before the last move, where its variations start
+    let mut leading = None; // comment waiting for the next move
+
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Move(san) => {
+                let ply = first_ply + line.len();
+                let m = board.parse_san(&san).map_err(|e| PgnError::BadMove(ply, san, e))?;
+                previous = board.clone();
+                let _ = board.try_move(m); // parse_san only offers legal moves
+
+                let mut node = Node::new(m);
+                node.before = leading.take();
+                line.push(node);
+            },
+            Token::Nag(nag) => {
+                if let Some(node) = line.last_mut() {
+                    node.nags.push(nag);
+                }
+            },
+            Token::Comment(comment) => match line.last_mut() {
+                Some(node) if leading.is_none() => append_comment(&mut node.after, comment),
+                _ => append_comment(&mut leading, comment),
+            },
+            Token::Open => {
+                let ply = first_ply + line.len();
+                let node = line.last_mut().ok_or(PgnError::UnbalancedVariation)?;
+                let variation = parse_line(tokens, &previous, ply - 1, true)?;
+                node.variations.push(variation);
+            },
+            Token::Close if nested => return Ok(line),
+            Token::Close => return Err(PgnError::UnbalancedVariation),
+        }
+    }
+
+    if nested {
+        return Err(PgnError::UnbalancedVariation);
+    }
+
+    Ok(line)
+}
+
+// one game: tag pairs, then movetext
+impl FromStr for PgnGame {
+    type Err = PgnError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut tags = Tags::default();
+        let mut movetext = String::new();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.starts_with('[') && movetext.trim().is_empty() {
+                let (name, value) = parse_tag(line).ok_or_else(|| PgnError::BadTag(line.to_string()))?;
+                tags.set(&name, &value);
+            } else if !line.starts_with('%') { // escaped line
+                movetext.push_str(line);
+                movetext.push('\n');
+            }
+        }
+
+        let fen = tags.get("FEN").unwrap_or(START_FEN).to_string();
+        let start = Board::from_fen_lenient(&fen).map_err(|_| PgnError::BadFen(fen))?;
+        let mainline = parse_line(&mut tokenize(&movetext).into_iter(), &start, 0, false)?;
+
+        Ok(PgnGame { tags, start, mainline })
+    }
+}
+
+// movetext tokens for line played from board: move numbers where white moves, and where
+// black's move follows a comment or variation ("12... Nf6")
+fn write_line(board: &Board, line: &[Node], style: PieceStyle, out: &mut Vec<String>) {
+    let mut board = board.clone();
+    let mut numbered = false;
+
+    for node in line {
+        if let Some(comment) = &node.before {
+            out.push(format!("{{{}}}", comment));
+            numbered = false;
+        }
+
+        match board.to_play {
+            Color::White => out.push(format!("{}.", board.fullmove_number)),
+            Color::Black if !numbered => out.push(format!("{}...", board.fullmove_number)),
+            Color::Black => (),
+        }
+        out.push(board.san_styled(&node.moveop, style));
+        out.extend(node.nags.iter().map(|nag| format!("${}", nag)));
+        numbered = true;
+
+        if let Some(comment) = &node.after {
+            out.push(format!("{{{}}}", comment));
+            numbered = false;
+        }
+
+        for variation in &node.variations {
+            let mut inner = Vec::new();
+            write_line(&board, variation, style, &mut inner);
+            if let Some(first) = inner.first_mut() {
+                first.insert(0, '(');
+            }
+            if let Some(last) = inner.last_mut() {
+                last.push(')');
+            }
+            out.extend(inner);
+            numbered = false;
+        }
+
+        let _ = board.try_move(node.moveop); // checked when the game was read
+    }
+}
+
+impl PgnGame {
+    // the export with the moves in style. figurine PGN is for reading, parse_san takes it
+    // back but most other programs won't
+    pub fn to_string_styled(&self, style: PieceStyle) -> String {
+        let mut text = String::new();
+        let _ = self.write(&mut text, style); // writing to a String can't fail
+        text
+    }
+
+    // export format: tags, a blank line, then movetext wrapped before 80 columns and ending in the result
+    fn write(&self, f: &mut impl fmt::Write, style: PieceStyle) -> fmt::Result {
+        writeln!(f, "{}", self.tags)?;
+
+        let mut tokens = Vec::new();
+        write_line(&self.start, &self.mainline, style, &mut tokens);
+        tokens.push(self.tags.get("Result").unwrap_or("*").to_string());
+
+        let mut column = 0;
+        for token in tokens {
+            let width = token.chars().count();
+            if column > 0 && column + 1 + width >= 80 {
+                writeln!(f)?;
+                column = 0;
+            }
+            if column > 0 {
+                write!(f, " ")?;
+                column += 1;
+            }
+            write!(f, "{}", token)?;
+            column += width;
+        }
+
+        writeln!(f)
+    }
+}
+
+impl fmt::Display for PgnGame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, PieceStyle::Letter)
+    }
+}
+
+// a whole file of games, e.g. a Lichess download. a new game starts at a tag pair after movetext
+pub fn read_games(text: &str) -> Vec<Result<PgnGame, PgnError>> {
+    let mut games = Vec::new();
+    let mut current = String::new();
+    let mut in_movetext = false;
+
+    for line in text.lines() {
+        let is_tag = line.trim_start().starts_with('[');
+        if is_tag && in_movetext {
+            games.push(current.parse());
+            current.clear();
+            in_movetext = false;
+        }
+        in_movetext |= !is_tag && !line.trim().is_empty();
+
+        current.push_str(line);
+        current.push('\n');
+    }
+    if !current.trim().is_empty() {
+        games.push(current.parse());
+    }
+
+    games
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::pgn::*;
+    #[test]
+    fn tags_test() {
+        let mut tags = Tags::default();
+        tags.set("WhiteElo", "2785");
+        tags.set("White", "Fischer, Robert J.");
+        tags.set("Annotator", "Someone \"quoted\"");
+        tags.set_date(Some(1972), Some(7), None);
+
+        assert_eq!(tags.get("Date"), Some("1972.07.??"));
+        assert_eq!(tags.get("WhiteElo"), Some("2785"));
+        assert_eq!(tags.get("ECO"), None);
+
+        assert_eq!(tags.to_string(), concat!(
+            "[Event \"?\"]\n",
+            "[Site \"?\"]\n",
+            "[Date \"1972.07.??\"]\n",
+            "[Round \"?\"]\n",
+            "[White \"Fischer, Robert J.\"]\n",
+            "[Black \"?\"]\n",
+            "[Result \"*\"]\n",
+            "[Annotator \"Someone \\\"quoted\\\"\"]\n",
+            "[WhiteElo \"2785\"]\n",
+        ));
+
+        tags.remove("White");
+        tags.remove("WhiteElo");
+        assert_eq!(tags.get("White"), Some("?"));
+        assert_eq!(tags.iter().count(), 8);
+    }
+
+    #[test]
+    fn import_test() {
+        let text = concat!(
+            "[Event \"Casual game\"]\n",
+            "[White \"A \\\"Quoted\\\" Player\"]\n",
+            "[Result \"1-0\"]\n",
+            "\n",
+            "1. e4 {best by test} e5 2. Nf3 (2. f4 exf4) Nc6 $1 3.Bb5 a6 4. Ba4 Nf6\n",
+            "5. O-O Be7 ; the main line\n",
+            "6. Re1 1-0\n",
+            "\n",
+            "[Event \"Second\"]\n",
+            "[SetUp \"1\"]\n",
+            "[FEN \"4k3/1P6/8/8/8/8/8/4K3 w - - 0 1\"]\n",
+            "\n",
+            "1. b8=Q+ Kd7 2. Qb7+ *\n",
+        );
+
+        let games = read_games(text);
+        assert_eq!(games.len(), 2);
+
+        let game = games[0].as_ref().unwrap();
+        assert_eq!(game.tags.get("White"), Some("A \"Quoted\" Player"));
+        assert_eq!(game.mainline.len(), 11);
+        assert!(game.position_at(4) == Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap());
+        let end = game.board();
+        assert_eq!(end.to_fen(), "r1bqk2r/1pppbppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQR1K1 b kq - 5 6");
+        assert_eq!(end.result, GameResult::WhiteResign);
+
+        let game = games[1].as_ref().unwrap();
+        assert_eq!(game.moves().len(), 3);
+        assert_eq!(game.board().to_fen(), "8/1Q1k4/8/8/8/8/8/4K3 b - - 2 2");
+
+        let bad: Result<PgnGame, _> = "1. e4 e5 2. Ke3".parse();
+        assert_eq!(bad.err(), Some(PgnError::BadMove(2, "Ke3".to_string(), SanError::NoMatch)));
+        assert_eq!("[Event ?]\n1. e4".parse::<PgnGame>().err(), Some(PgnError::BadTag("[Event ?]".to_string())));
+        assert_eq!("[FEN \"4k3/8/8/3nP3/8/8/8/4K3 w - d6 0 1\"]\n1. e6".parse::<PgnGame>().err(),
+                   Some(PgnError::BadFen("4k3/8/8/3nP3/8/8/8/4K3 w - d6 0 1".to_string())));
+    }
+
+    #[test]
+    fn variations_test() {
+        let text = concat!(
+            "[Event \"Study\"]\n",
+            "\n",
+            "{Open game} 1. e4 e5 2. Nf3 $1 {the main move} (2. f4 exf4 (2... d5) 3. Nf3 g5!?) 2... Nc6?! ",
+            "3. Bb5 (3. d4) 3... a6 *\n",
+        );
+        let game: PgnGame = text.parse().unwrap();
+        assert_eq!(game.mainline.len(), 6);
+
+        let nf3 = &game.mainline[2];
+        assert_eq!(nf3.nags, vec![1]);
+        assert_eq!(nf3.after.as_deref(), Some("the main move"));
+        assert_eq!(nf3.variations.len(), 1);
+        let gambit = &nf3.variations[0];
+        assert_eq!(gambit.len(), 4);
+        assert_eq!(gambit[1].variations[0].len(), 1); // 2... d5 instead of exf4
+        assert_eq!(gambit[3].nags, vec![5]);
+        assert_eq!(game.mainline[0].before.as_deref(), Some("Open game"));
+        assert_eq!(game.mainline[3].nags, vec![6]);
+
+        let exported = game.to_string();
+        assert!(exported.ends_with(concat!(
+            "\n\n{Open game} 1. e4 e5 2. Nf3 $1 {the main move} (2. f4 exf4 (2... d5) 3. Nf3 g5\n",
+            "$5) 2... Nc6 $6 3. Bb5 (3. d4) 3... a6 *\n",
+        )));
+        assert_eq!(exported.parse::<PgnGame>().unwrap().to_string(), exported);
+
+        // figurines, which read back as the same game
+        let figurines = game.to_string_styled(PieceStyle::Figurine);
+        assert!(figurines.contains("2. ♘f3 $1") && figurines.contains("2... ♞c6 $6 3. ♗b5"));
+        assert_eq!(figurines.parse::<PgnGame>().unwrap().to_string(), exported);
+
+        assert_eq!("1. e4 (1. d4".parse::<PgnGame>().err(), Some(PgnError::UnbalancedVariation));
+        assert_eq!("1. e4) e5".parse::<PgnGame>().err(), Some(PgnError::UnbalancedVariation));
+        assert_eq!("1. e4 (1... e5)".parse::<PgnGame>().err().map(|e| matches!(e, PgnError::BadMove(0, _, _))), Some(true));
+    }
+}
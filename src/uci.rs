@@ -0,0 +1,164 @@
+// UCI protocol helpers: coordinate move text ("e2e4", "e7e8q") and the
+// "position [startpos | fen <fen>] [moves ...]" command.
+use crate::board::{Board, MoveError, MoveOp, PieceType, START_FEN};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug,Clone,Eq,PartialEq)]
+pub enum PositionError {
+    MissingPosition,           // neither "startpos" nor "fen"
+    BadFen(String),
+    BadMove(String),           // not coordinate notation for any legal move
+    IllegalMove(String, MoveError),
+}
+
+// a move in UCI coordinates, independent of any board. MoveOp squares are indices that
+// depend on the board's width, so going between the two takes a board
+#[derive(Debug,Clone,Eq,PartialEq)]
+pub struct UciMove {
+    pub from: String,
+    pub to: String,
+    pub promote: PieceType,
+}
+
+impl UciMove {
+    pub fn new(board: &Board, moveop: &MoveOp) -> Self {
+        UciMove {
+            from: board.index_to_alg(moveop.from),
+            to: board.index_to_alg(moveop.to),
+            promote: moveop.promote,
+        }
+    }
+
+    // the legal move this stands for in board's position
+    pub fn to_move(&self, board: &Board) -> Option<MoveOp> {
+        let from = board.parse_square(&self.from)?;
+        let to = board.parse_square(&self.to)?;
+
+        board.clone().legal_moves_from(from).iter()
+            .find(|m| m.to == to && m.promote == self.promote)
+            .copied()
+    }
+}
+
+impl FromStr for UciMove {
+    type Err = PositionError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let bad = || PositionError::BadMove(text.to_string());
+
+        // the destination starts at the second file letter, ranks may have two digits on big boards
+        let split = text.char_indices().skip(1).find(|(_, c)| c.is_ascii_lowercase()).ok_or_else(bad)?.0;
+        let (from, rest) = text.split_at(split);
+        let (to, promote) = match rest.chars().last() {
+            Some(c) if c.is_ascii_lowercase() && rest.len() > 2 => {
+                let promote = PieceType::from_letter(c.to_ascii_uppercase()).ok_or_else(bad)?;
+                (&rest[..rest.len() - 1], promote)
+            },
+            _ => (rest, PieceType::Empty),
+        };
+
+        // a file letter then rank digits, whether the square is on the board is up to to_move
+        let square = |name: &str| {
+            let mut chars = name.chars();
+            matches!(chars.next(), Some('a'..='z')) && !chars.as_str().is_empty() && chars.all(|c| c.is_ascii_digit())
+        };
+        if !square(from) || !square(to) || matches!(promote, PieceType::Pawn | PieceType::King) {
+            return Err(bad());
+        }
+
+        Ok(UciMove { from: from.to_string(), to: to.to_string(), promote })
+    }
+}
+
+// castling is written as the king's step, promotions with a lowercase letter
+impl fmt::Display for UciMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.from, self.to)?;
+        if self.promote != PieceType::Empty {
+            write!(f, "{}", self.promote.letter().to_ascii_lowercase())?;
+        }
+
+        Ok(())
+    }
+}
+
+// the legal move spelled by UCI coordinate text in board's position
+pub fn parse_move(board: &Board, text: &str) -> Option<MoveOp> {
+    text.parse::<UciMove>().ok()?.to_move(board)
+}
+
+// UCI coordinate text for a move
+pub fn move_text(board: &Board, moveop: &MoveOp) -> String {
+    UciMove::new(board, moveop).to_string()
+}
+
+// board for a "position ..." command, the leading "position" keyword is optional
+pub fn position(command: &str) -> Result<Board, PositionError> {
+    let mut tokens = command.split_whitespace().peekable();
+    tokens.next_if_eq(&"position");
+
+    let mut board = match tokens.next() {
+        Some("startpos") => Board::from_fen(START_FEN).unwrap(),
+        Some("fen") => {
+            let fen: Vec<&str> = std::iter::from_fn(|| tokens.next_if(|&t| t != "moves")).collect();
+            let fen = fen.join(" ");
+            Board::from_fen_lenient(&fen).map_err(|_| PositionError::BadFen(fen))?
+        },
+        _ => return Err(PositionError::MissingPosition),
+    };
+
+    if tokens.next_if_eq(&"moves").is_some() {
+        for text in tokens {
+            let moveop = parse_move(&board, text).ok_or_else(|| PositionError::BadMove(text.to_string()))?;
+            board.try_move(moveop).map_err(|e| PositionError::IllegalMove(text.to_string(), e))?;
+        }
+    }
+
+    Ok(board)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::board::*;
+    use crate::uci::*;
+    #[test]
+    fn position_test() {
+        let expected = Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2").unwrap();
+        assert!(position("position startpos moves e2e4 e7e5 g1f3").unwrap() == expected);
+        assert!(position("startpos moves e2e4 e7e5 g1f3").unwrap() == expected);
+        assert!(position("fen rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2 moves g1f3").unwrap() == expected);
+        assert!(position("position startpos").unwrap() == Board::from_fen(START_FEN).unwrap());
+
+        assert_eq!(position("position").err(), Some(PositionError::MissingPosition));
+        assert_eq!(position("position fen 8/7 w - - 0 1").err(), Some(PositionError::BadFen("8/7 w - - 0 1".to_string())));
+        assert_eq!(position("position startpos moves e2e5").err(), Some(PositionError::BadMove("e2e5".to_string())));
+        assert_eq!(position("fen 4k3/8/8/4P3/8/8/8/4K3 w - d6 0 1").err(), Some(PositionError::BadFen("4k3/8/8/4P3/8/8/8/4K3 w - d6 0 1".to_string())));
+
+        let board = Board::from_fen(START_FEN).unwrap();
+        let m = parse_move(&board, "b1c3").unwrap();
+        assert_eq!((m.from, m.to), (57, 42));
+        assert_eq!(move_text(&board, &m), "b1c3");
+        assert_eq!(move_text(&board, &MoveOp{from: 12, to: 4, promote: PieceType::Queen, ..Default::default()}), "e7e8q");
+    }
+
+    #[test]
+    fn uci_move_test() {
+        let m: UciMove = "e7e8q".parse().unwrap();
+        assert_eq!(m, UciMove{from: "e7".to_string(), to: "e8".to_string(), promote: PieceType::Queen});
+        assert_eq!(m.to_string(), "e7e8q");
+        assert_eq!("b10b9".parse::<UciMove>().map(|m| m.to_string()), Ok("b10b9".to_string()));
+
+        for bad in ["", "e2", "e2e", "e2e4k", "e2e4x", "22e4", "e2e4 "] {
+            assert_eq!(bad.parse::<UciMove>(), Err(PositionError::BadMove(bad.to_string())));
+        }
+
+        let board = Board::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let m: UciMove = "b7b8n".parse().unwrap();
+        let moveop = m.to_move(&board).unwrap();
+        assert_eq!((moveop.from, moveop.to, moveop.promote), (9, 1, PieceType::Knight));
+        assert_eq!(UciMove::new(&board, &moveop), m);
+        assert_eq!("b7b8".parse::<UciMove>().unwrap().to_move(&board), None);
+    }
+}
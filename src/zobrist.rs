@@ -0,0 +1,73 @@
+// Zobrist keys for position hashing. Generated from a fixed seed so keys are
+// stable across runs (and can be stored, e.g. in opening books).
+use lazy_static::lazy_static;
+
+use crate::board::{Color, PieceType, MAX_FILES, PIECE_TYPES};
+
+pub const MAX_SQUARES: usize = 128; // same limit as board::Bitboard
+
+pub struct ZobristKeys {
+    pub pieces: [[[u64; MAX_SQUARES]; PIECE_TYPES]; 2], // [color][piece type][square]
+    pub black_to_play: u64,
+    pub castling: [u64; 4], // KQkq
+    pub en_passant: [u64; MAX_FILES], // by file
+    pub fairy_seed: u64, // fairy keys are worked out from it on demand, drawn last so the rest don't move
+}
+
+const GOLDEN_GAMMA: u64 = 0x9e3779b97f4a7c15;
+
+// splitmix64's output function
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+lazy_static! {
+    pub static ref ZOBRIST: ZobristKeys = ZobristKeys::new(0x5eed_c4e5_5b0a_4d01);
+}
+
+impl ZobristKeys {
+    fn new(seed: u64) -> Self {
+        let mut state = seed;
+        let mut next = || {
+            // splitmix64
+            state = state.wrapping_add(GOLDEN_GAMMA);
+            mix(state)
+        };
+
+        let mut keys = ZobristKeys {
+            pieces: [[[0; MAX_SQUARES]; PIECE_TYPES]; 2],
+            black_to_play: 0,
+            castling: [0; 4],
+            en_passant: [0; MAX_FILES],
+            fairy_seed: 0,
+        };
+
+        for color in keys.pieces.iter_mut() {
+            for piece in color.iter_mut() {
+                for key in piece.iter_mut() {
+                    *key = next();
+                }
+            }
+        }
+
+        keys.black_to_play = next();
+        keys.castling.iter_mut().for_each(|key| *key = next());
+        keys.en_passant.iter_mut().for_each(|key| *key = next());
+        keys.fairy_seed = next();
+
+        keys
+    }
+
+    pub fn piece(&self, color: Color, piece: PieceType, square: usize) -> u64 {
+        match piece {
+            // the n-th number of a splitmix64 stream seeded with fairy_seed, a table would cap the slots
+            PieceType::Fairy(slot) => {
+                let n = ((slot as usize * 2 + color as usize) * MAX_SQUARES + square) as u64;
+                mix(self.fairy_seed.wrapping_add(GOLDEN_GAMMA.wrapping_mul(n + 1)))
+            },
+            _ => self.pieces[color as usize][piece.index()][square],
+        }
+    }
+}